@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -13,6 +13,21 @@ pub struct Cli {
     /// Path to the configuration file
     #[arg(short, long, global = true)]
     pub config: Option<PathBuf>,
+
+    /// More log output (-v debug, -vv trace)
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Less log output (-q warnings, -qq errors only)
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "verbose")]
+    pub quiet: u8,
+}
+
+impl Cli {
+    /// Steps to move the configured log level by.
+    pub fn verbosity(&self) -> i8 {
+        self.verbose as i8 - self.quiet as i8
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -30,6 +45,14 @@ pub enum Commands {
         /// Sheet name for Excel/ODS files
         #[arg(short, long)]
         sheet: Option<String>,
+
+        /// Write a JSON run report to this file, or the plan with --dry-run
+        #[arg(long)]
+        report: Option<PathBuf>,
+
+        /// Print the run report to stdout as JSON
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output_format: OutputFormat,
     },
 
     /// Process all files in a directory
@@ -49,6 +72,22 @@ pub enum Commands {
         /// Process subdirectories recursively
         #[arg(short, long)]
         recursive: bool,
+
+        /// Stop at the first file that fails to convert
+        #[arg(long)]
+        fail_fast: bool,
+
+        /// Show what would be converted, and where, without reading or writing files
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Write a JSON run report to this file, or the plan with --dry-run
+        #[arg(long)]
+        report: Option<PathBuf>,
+
+        /// Print the run report to stdout as JSON
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output_format: OutputFormat,
     },
 
     /// Watch a directory and convert files automatically
@@ -68,6 +107,83 @@ pub enum Commands {
         /// Watch subdirectories recursively
         #[arg(short, long)]
         recursive: bool,
+
+        /// Serve /metrics and /healthz on this address, e.g. 0.0.0.0:9090 (overrides config)
+        #[arg(long)]
+        metrics_listen: Option<String>,
+    },
+
+    /// Receive MinIO/S3 bucket notifications over HTTP and convert new objects
+    Serve {
+        /// Address to listen on, e.g. 0.0.0.0:8080 (overrides config)
+        #[arg(short, long)]
+        listen: Option<String>,
+
+        /// Output directory or s3:// URI (overrides config)
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+
+        /// Delete source objects after conversion
+        #[arg(long)]
+        delete_source: bool,
+    },
+
+    /// Show the detected format, schema and first rows of a file without converting it
+    Inspect {
+        /// Input file path
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Sheet name for Excel/ODS files
+        #[arg(short, long)]
+        sheet: Option<String>,
+
+        /// Number of rows to show
+        #[arg(short = 'n', long, default_value_t = 10)]
+        rows: usize,
+
+        /// Print the result as JSON
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output_format: OutputFormat,
+    },
+
+    /// Show the metadata of written Parquet files: row groups, codecs and statistics
+    ParquetInfo {
+        /// Parquet files, or directories holding them
+        #[arg(short, long, num_args = 1.., required = true)]
+        input: Vec<PathBuf>,
+
+        /// Print the metadata as JSON
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        output_format: OutputFormat,
+    },
+
+    /// Compare a source file with the Parquet file or dataset written from it
+    Verify {
+        /// Source file path
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Parquet file or partitioned dataset directory (defaults to the input with a .parquet extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Sheet name for Excel/ODS files
+        #[arg(short, long)]
+        sheet: Option<String>,
+
+        /// Also compare a checksum of every column's values
+        #[arg(long)]
+        checksum: bool,
+    },
+
+    /// Check the configuration file and the directories it uses
+    ValidateConfig,
+
+    /// Inspect the effective configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
     },
 
     /// Generate a default configuration file
@@ -77,3 +193,17 @@ pub enum Commands {
         output: PathBuf,
     },
 }
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Log lines only
+    Text,
+    /// The run report as JSON on stdout
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the configuration after environment overrides, with secrets masked
+    Show,
+}
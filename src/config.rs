@@ -1,22 +1,73 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::filter::FileFilter;
+use crate::format::FileFormat;
+use crate::logging;
+use crate::quality::QualityChecks;
+use crate::processor::read_options;
+use crate::reader::{self, BadLinePolicy, ReadOptions};
+use crate::rules::{self, RuleSet};
+use crate::schema;
+use crate::storage;
+use crate::template;
+
+mod env;
+
 const DEFAULT_CONFIG_NAME: &str = "config.toml";
 const DEFAULT_POLL_INTERVAL_SECS: u64 = 2;
 const DEFAULT_DEBOUNCE_MS: u64 = 500;
+const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:8080";
+const DEFAULT_INFER_SCHEMA_ROWS: usize = 1000;
+const DEFAULT_LOG_LEVEL: &str = "info";
+const DEFAULT_SOURCE_FILE_COLUMN: &str = "_source_file";
+const DEFAULT_SOURCE_SHEET_COLUMN: &str = "_source_sheet";
+const DEFAULT_ROW_NUMBER_COLUMN: &str = "_source_row_number";
+const DEFAULT_INGESTED_AT_COLUMN: &str = "_ingested_at";
+const DEFAULT_REASON_COLUMN: &str = "_reject_reason";
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     #[serde(default)]
     pub general: GeneralConfig,
 
     #[serde(default)]
     pub watch: WatchConfig,
+
+    #[serde(default)]
+    pub processing: ProcessingConfig,
+
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    #[serde(default)]
+    pub serve: ServeConfig,
+
+    #[serde(default)]
+    pub logging: LoggingConfig,
+
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    #[serde(default)]
+    pub lineage: LineageConfig,
+
+    #[serde(default)]
+    pub quality: QualityConfig,
+
+    #[serde(default)]
+    pub locale: LocaleConfig,
+
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct GeneralConfig {
     #[serde(default = "default_input_dir")]
     pub input_dir: PathBuf,
@@ -32,15 +83,362 @@ pub struct GeneralConfig {
 
     #[serde(default)]
     pub default_sheet: Option<String>,
+
+    #[serde(default)]
+    pub output_template: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct WatchConfig {
     #[serde(default = "default_poll_interval")]
     pub poll_interval_secs: u64,
 
     #[serde(default = "default_debounce_ms")]
     pub debounce_ms: u64,
+
+    #[serde(default)]
+    pub backend: WatchBackend,
+
+    #[serde(default)]
+    pub state_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchBackend {
+    /// Filesystem notifications (inotify, FSEvents, ...)
+    #[default]
+    Native,
+    /// Periodic directory rescan, for NFS/SMB/s3fs mounts that emit no events
+    Poll,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessingConfig {
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    #[serde(default)]
+    pub min_file_size: Option<u64>,
+
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+
+    /// CSV dialect settings; each one not set is sniffed from the start of the file
+    #[serde(default)]
+    pub csv_delimiter: Option<char>,
+
+    #[serde(default)]
+    pub csv_has_header: Option<bool>,
+
+    #[serde(default)]
+    pub csv_quote_char: Option<char>,
+
+    /// Numbers are written as `1,5` instead of `1.5`
+    #[serde(default)]
+    pub csv_decimal_comma: Option<bool>,
+
+    #[serde(default)]
+    pub csv_bad_lines: BadLinePolicy,
+
+    /// Drop the fields past the header's count instead of treating the line as bad
+    #[serde(default)]
+    pub csv_truncate_ragged_lines: bool,
+
+    /// Read empty fields as null rather than as empty strings
+    #[serde(default = "default_true")]
+    pub csv_missing_is_null: bool,
+
+    /// Encoding of CSV and XML files, e.g. `windows-1252`; detected when not set
+    #[serde(default)]
+    pub encoding: Option<String>,
+
+    #[serde(default = "default_infer_schema_rows")]
+    pub infer_schema_rows: usize,
+
+    #[serde(default)]
+    pub verify: VerifyMode,
+}
+
+/// Whether a written Parquet file is read back and compared with the source data.
+/// A file that fails verification counts as failed, so its source is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyMode {
+    #[default]
+    Off,
+    /// Row count, column names and data types
+    Basic,
+    /// Basic checks plus a checksum of every column's values
+    Checksum,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_level")]
+    pub level: String,
+
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Log each file as it starts converting
+    #[serde(default = "default_true")]
+    pub show_progress: bool,
+
+    /// Progress bar for batch runs, shown only on a terminal
+    #[serde(default = "default_true")]
+    pub progress_bar: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for journald or Loki
+    Json,
+}
+
+/// Prometheus endpoint for watch mode, disabled unless `listen` is set.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Address for `/metrics` and `/healthz`, e.g. "0.0.0.0:9090"
+    #[serde(default)]
+    pub listen: Option<String>,
+}
+
+/// Row-level provenance columns appended to every output when `enabled`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LineageConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Source path or `s3://` URI
+    #[serde(default = "default_source_file_column")]
+    pub source_file: String,
+
+    /// Sheet name for workbooks, null for other formats
+    #[serde(default = "default_source_sheet_column")]
+    pub source_sheet: String,
+
+    /// 1-based position among the records read, before quality checks; not a line number
+    #[serde(default = "default_row_number_column")]
+    pub row_number: String,
+
+    /// Conversion time, UTC
+    #[serde(default = "default_ingested_at_column")]
+    pub ingested_at: String,
+}
+
+/// Per-column data quality checks. Rows failing any check are written to a
+/// `<output>_rejects.parquet` next to the output instead of the output itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QualityConfig {
+    /// Fail the whole file when more than this percentage of its rows is rejected
+    #[serde(default)]
+    pub max_reject_percent: Option<f64>,
+
+    /// Column added to the rejects file with the failed checks
+    #[serde(default = "default_reason_column")]
+    pub reason_column: String,
+
+    #[serde(default)]
+    pub columns: BTreeMap<String, ColumnChecks>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnChecks {
+    #[serde(default)]
+    pub not_null: bool,
+
+    /// Values, as text, must match this regex
+    #[serde(default)]
+    pub regex: Option<String>,
+
+    /// Values must be numeric and at least `min`
+    #[serde(default)]
+    pub min: Option<f64>,
+
+    /// Values must be numeric and at most `max`
+    #[serde(default)]
+    pub max: Option<f64>,
+
+    /// Values, as text, must be one of these
+    #[serde(default)]
+    pub allowed: Vec<String>,
+
+    /// Values must not repeat within the file
+    #[serde(default)]
+    pub unique: bool,
+}
+
+/// How text values are read when a schema file types them as numbers, dates or
+/// datetimes, e.g. `1.234,56` and `31/12/2024`. A schema file column can override
+/// any of these.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LocaleConfig {
+    #[serde(default)]
+    pub decimal_separator: Option<char>,
+
+    #[serde(default)]
+    pub thousands_separator: Option<char>,
+
+    /// chrono `strftime` formats, tried in order
+    #[serde(default)]
+    pub date_formats: Vec<String>,
+
+    #[serde(default)]
+    pub datetime_formats: Vec<String>,
+}
+
+/// A `[[rules]]` entry. The first rule whose `glob` or `regex` matches the path
+/// relative to the input directory decides how the file is converted.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    #[serde(default)]
+    pub name: Option<String>,
+
+    #[serde(default)]
+    pub glob: Option<String>,
+
+    #[serde(default)]
+    pub regex: Option<String>,
+
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+
+    #[serde(default)]
+    pub output_template: Option<String>,
+
+    #[serde(default)]
+    pub schema_file: Option<PathBuf>,
+
+    #[serde(default)]
+    pub partition_by: Vec<String>,
+
+    #[serde(default)]
+    pub post_action: Option<PostAction>,
+
+    #[serde(default)]
+    pub move_to: Option<PathBuf>,
+
+    #[serde(default)]
+    pub reader: ReaderOverrides,
+
+    /// Replaces the top-level `[quality]` checks for matching files
+    #[serde(default)]
+    pub quality: Option<QualityConfig>,
+
+    /// Replaces the top-level `[locale]` settings for matching files
+    #[serde(default)]
+    pub locale: Option<LocaleConfig>,
+}
+
+/// Per-rule reader settings. Unset values fall back to `[processing]` and `[general]`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ReaderOverrides {
+    #[serde(default)]
+    pub sheet: Option<String>,
+
+    #[serde(default)]
+    pub csv_delimiter: Option<char>,
+
+    #[serde(default)]
+    pub csv_has_header: Option<bool>,
+
+    #[serde(default)]
+    pub csv_quote_char: Option<char>,
+
+    #[serde(default)]
+    pub csv_decimal_comma: Option<bool>,
+
+    #[serde(default)]
+    pub csv_bad_lines: Option<BadLinePolicy>,
+
+    #[serde(default)]
+    pub csv_truncate_ragged_lines: Option<bool>,
+
+    #[serde(default)]
+    pub csv_missing_is_null: Option<bool>,
+
+    #[serde(default)]
+    pub encoding: Option<String>,
+
+    #[serde(default)]
+    pub infer_schema_rows: Option<usize>,
+
+    #[serde(default)]
+    pub xml_record_path: Option<String>,
+
+    #[serde(default)]
+    pub json_pointer: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PostAction {
+    Keep,
+    Delete,
+    Move,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct StorageConfig {
+    #[serde(default)]
+    pub backend: StorageBackend,
+
+    #[serde(default)]
+    pub endpoint: Option<String>,
+
+    #[serde(default)]
+    pub region: Option<String>,
+
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+
+    #[serde(default)]
+    pub allow_http: bool,
+
+    #[serde(default)]
+    pub virtual_hosted_style: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    S3,
+    Memory,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServeConfig {
+    #[serde(default = "default_listen_addr")]
+    pub listen: String,
+
+    #[serde(default)]
+    pub auth_token: Option<String>,
 }
 
 fn default_input_dir() -> PathBuf {
@@ -51,6 +449,49 @@ fn default_output_dir() -> PathBuf {
     PathBuf::from("./output")
 }
 
+fn default_extensions() -> Vec<String> {
+    FileFormat::supported_extensions()
+        .iter()
+        .map(|ext| ext.to_string())
+        .collect()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_infer_schema_rows() -> usize {
+    DEFAULT_INFER_SCHEMA_ROWS
+}
+
+fn default_listen_addr() -> String {
+    DEFAULT_LISTEN_ADDR.to_string()
+}
+
+fn default_log_level() -> String {
+    DEFAULT_LOG_LEVEL.to_string()
+}
+
+fn default_source_file_column() -> String {
+    DEFAULT_SOURCE_FILE_COLUMN.to_string()
+}
+
+fn default_source_sheet_column() -> String {
+    DEFAULT_SOURCE_SHEET_COLUMN.to_string()
+}
+
+fn default_row_number_column() -> String {
+    DEFAULT_ROW_NUMBER_COLUMN.to_string()
+}
+
+fn default_ingested_at_column() -> String {
+    DEFAULT_INGESTED_AT_COLUMN.to_string()
+}
+
+fn default_reason_column() -> String {
+    DEFAULT_REASON_COLUMN.to_string()
+}
+
 fn default_poll_interval() -> u64 {
     DEFAULT_POLL_INTERVAL_SECS
 }
@@ -67,6 +508,7 @@ impl Default for GeneralConfig {
             recursive: false,
             delete_source: false,
             default_sheet: None,
+            output_template: None,
         }
     }
 }
@@ -76,10 +518,88 @@ impl Default for WatchConfig {
         Self {
             poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
             debounce_ms: DEFAULT_DEBOUNCE_MS,
+            backend: WatchBackend::default(),
+            state_file: None,
+        }
+    }
+}
+
+impl Default for ProcessingConfig {
+    fn default() -> Self {
+        Self {
+            extensions: default_extensions(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            min_file_size: None,
+            max_file_size: None,
+            csv_delimiter: None,
+            csv_has_header: None,
+            csv_quote_char: None,
+            csv_decimal_comma: None,
+            csv_bad_lines: BadLinePolicy::Error,
+            csv_truncate_ragged_lines: false,
+            csv_missing_is_null: true,
+            encoding: None,
+            infer_schema_rows: DEFAULT_INFER_SCHEMA_ROWS,
+            verify: VerifyMode::Off,
+        }
+    }
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        Self {
+            listen: default_listen_addr(),
+            auth_token: None,
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            format: LogFormat::default(),
+            show_progress: true,
+            progress_bar: true,
+        }
+    }
+}
+
+impl Default for LineageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source_file: default_source_file_column(),
+            source_sheet: default_source_sheet_column(),
+            row_number: default_row_number_column(),
+            ingested_at: default_ingested_at_column(),
         }
     }
 }
 
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            max_reject_percent: None,
+            reason_column: default_reason_column(),
+            columns: BTreeMap::new(),
+        }
+    }
+}
+
+impl LineageConfig {
+    /// Column names in the order they are appended.
+    pub fn columns(&self) -> [&str; 4] {
+        [
+            &self.source_file,
+            &self.source_sheet,
+            &self.row_number,
+            &self.ingested_at,
+        ]
+    }
+}
+
 impl GeneralConfig {
     pub fn apply_overrides(
         &mut self,
@@ -104,19 +624,167 @@ impl GeneralConfig {
 }
 
 impl Config {
+    /// Reads the file (or the defaults) and applies `PARSE_TO_PARQUET__*` environment
+    /// overrides. CLI flags are applied on top by the caller.
     pub fn load(path: Option<&Path>) -> Result<Self> {
-        let config_path = path
-            .map(PathBuf::from)
-            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_NAME));
+        let config = Self::load_file(path)?;
+
+        let vars = std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
 
-        if config_path.exists() {
-            let content = fs::read_to_string(&config_path)
-                .with_context(|| format!("Failed to read config file: {:?}", config_path))?;
+        env::apply_env(config, vars)
+    }
+
+    fn load_file(path: Option<&Path>) -> Result<Self> {
+        let config_path = match path {
+            Some(path) if !path.exists() => {
+                return Err(anyhow!("Config file not found: {}", path.display()));
+            }
+            Some(path) => path.to_path_buf(),
+            None => PathBuf::from(DEFAULT_CONFIG_NAME),
+        };
 
-            toml::from_str(&content).context("Failed to parse config file")
-        } else {
-            Ok(Self::default())
+        // Only the implicit ./config.toml is optional
+        if !config_path.exists() {
+            return Ok(Self::default());
         }
+
+        let content = fs::read_to_string(&config_path)
+            .with_context(|| format!("Failed to read config file: {:?}", config_path))?;
+
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file: {}", config_path.display()))
+    }
+
+    /// Checks that the directories the configuration points at exist and are writable.
+    /// Returns one message per problem; remote `s3://` locations are not checked.
+    pub fn check_paths(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let input_dir = &self.general.input_dir;
+
+        if !storage::is_remote(input_dir) && !input_dir.is_dir() {
+            problems.push(format!(
+                "general.input_dir does not exist: {}",
+                input_dir.display()
+            ));
+        }
+
+        let mut writable = vec![("general.output_dir".to_string(), self.general.output_dir.clone())];
+
+        if let Some(ref state_file) = self.watch.state_file {
+            if let Some(parent) = state_file.parent() {
+                writable.push(("watch.state_file".to_string(), parent.to_path_buf()));
+            }
+        }
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            let label = match rule.name {
+                Some(ref name) => format!("rule '{}'", name),
+                None => format!("rule #{}", index + 1),
+            };
+
+            if let Some(ref dir) = rule.output_dir {
+                writable.push((format!("{} output_dir", label), dir.clone()));
+            }
+            if let Some(ref dir) = rule.move_to {
+                writable.push((format!("{} move_to", label), dir.clone()));
+            }
+            if let Some(ref schema) = rule.schema_file {
+                if !schema.is_file() {
+                    problems.push(format!(
+                        "{} schema_file does not exist: {}",
+                        label,
+                        schema.display()
+                    ));
+                }
+            }
+        }
+
+        for (key, dir) in writable {
+            if storage::is_remote(&dir) {
+                continue;
+            }
+            if let Err(e) = check_writable(&dir) {
+                problems.push(format!("{} is not writable: {} ({:#})", key, dir.display(), e));
+            }
+        }
+
+        problems
+    }
+
+    /// Checks settings that deserialize fine but cannot be used, such as invalid patterns.
+    pub fn validate(&self) -> Result<()> {
+        if self.processing.csv_delimiter.is_some_and(|c| !c.is_ascii()) {
+            return Err(anyhow!(
+                "processing.csv_delimiter must be a single ASCII character"
+            ));
+        }
+
+        if self.processing.csv_quote_char.is_some_and(|c| !c.is_ascii()) {
+            return Err(anyhow!(
+                "processing.csv_quote_char must be a single ASCII character"
+            ));
+        }
+
+        if decimal_comma_clash(&read_options(self, None)) {
+            return Err(anyhow!(
+                "processing.csv_decimal_comma cannot be used with csv_delimiter = ','"
+            ));
+        }
+
+        if let Some(ref encoding) = self.processing.encoding {
+            reader::validate_encoding(encoding).context("Invalid processing.encoding")?;
+        }
+
+        logging::parse_level(&self.logging.level)?;
+
+        if let Some(ref output_template) = self.general.output_template {
+            template::validate(output_template)?;
+        }
+
+        FileFilter::new(&self.processing)?;
+        RuleSet::new(&self.rules)?;
+        for (index, rule) in self.rules.iter().enumerate() {
+            // A rule may set one of the two and inherit the other from [processing].
+            if decimal_comma_clash(&read_options(self, Some(rule))) {
+                return Err(anyhow!(
+                    "Invalid rule {}: csv_decimal_comma cannot be used with csv_delimiter = ',' \
+                     (one of them may come from [processing])",
+                    rules::rule_label(rule, index)
+                ));
+            }
+        }
+        QualityChecks::new(&self.quality).context("Invalid [quality] section")?;
+        schema::validate_locale(&self.locale).context("Invalid [locale] section")?;
+
+        let columns = self.lineage.columns();
+        for (index, name) in columns.iter().enumerate() {
+            if name.is_empty() {
+                return Err(anyhow!("lineage column names must not be empty"));
+            }
+            if columns[..index].contains(name) {
+                return Err(anyhow!("Duplicate lineage column name: {}", name));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A copy for display, with credentials replaced.
+    pub fn masked(&self) -> Self {
+        let mask = |value: &Option<String>| value.as_ref().map(|_| "********".to_string());
+
+        let mut config = self.clone();
+        config.storage.secret_access_key = mask(&config.storage.secret_access_key);
+        config.serve.auth_token = mask(&config.serve.auth_token);
+        config
+    }
+
+    /// SHA-256 of the effective configuration with secrets masked, to tell which
+    /// settings produced a file.
+    pub fn fingerprint(&self) -> String {
+        let content = toml::to_string(&self.masked()).unwrap_or_default();
+        template::hash_bytes(content.as_bytes())
     }
 
     pub fn save(&self, path: &Path) -> Result<()> {
@@ -131,3 +799,127 @@ impl Config {
         Self::default().save(path)
     }
 }
+
+/// Directories that do not exist yet are created on first use, so the closest
+/// existing ancestor is tested instead.
+fn check_writable(dir: &Path) -> Result<()> {
+    let existing = dir
+        .ancestors()
+        .find(|p| p.exists())
+        .unwrap_or(Path::new("."));
+
+    if !existing.is_dir() {
+        return Err(anyhow!("{} is not a directory", existing.display()));
+    }
+
+    tempfile::Builder::new()
+        .prefix(".write-test")
+        .tempfile_in(existing)
+        .map(|_| ())
+        .context("cannot create files")
+}
+
+/// Polars reads `1,5` as a decimal only when `,` is not also the field separator.
+fn decimal_comma_clash(options: &ReadOptions) -> bool {
+    options.csv_decimal_comma == Some(true) && options.csv_delimiter == Some(b',')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config> {
+        let config: Config = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        for text in [
+            "[general]\ninput_directory = \"in\"\n",
+            "[procesing]\ncsv_delimiter = \";\"\n",
+            "[[rules]]\nglob = \"*.csv\"\ndelimiter = \";\"\n",
+            "[[rules]]\nglob = \"*.csv\"\n[rules.reader]\nseparator = \";\"\n",
+        ] {
+            let error = toml::from_str::<Config>(text).unwrap_err().to_string();
+            assert!(error.contains("unknown field"), "{}: {}", text, error);
+        }
+    }
+
+    #[test]
+    fn unusable_settings_fail_validation() {
+        let cases = [
+            ("[processing]\ncsv_quote_char = \"é\"\n", "ASCII"),
+            (
+                "[processing]\nencoding = \"klingon\"\n",
+                "processing.encoding",
+            ),
+            ("[logging]\nlevel = \"loud\"\n", "loud"),
+            ("[general]\noutput_template = \"{nope}\"\n", "nope"),
+            (
+                "[lineage]\nsource_file = \"origin\"\nrow_number = \"origin\"\n",
+                "Duplicate lineage column name",
+            ),
+        ];
+
+        for (text, expected) in cases {
+            let error = format!("{:#}", parse(text).unwrap_err());
+            assert!(error.contains(expected), "{}: {}", text, error);
+        }
+
+        parse("").unwrap();
+    }
+
+    #[test]
+    fn check_paths_reports_each_problem() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+
+        let mut config = Config::default();
+        config.general.input_dir = dir.path().join("missing");
+        config.general.output_dir = dir.path().join("out/parquet");
+        config.rules = toml::from_str::<Config>(&format!(
+            "[[rules]]\nname = \"r\"\nglob = \"*\"\nschema_file = \"{0}/nope.toml\"\nmove_to = \"{0}/file/done\"\n",
+            dir.path().display()
+        ))
+        .unwrap()
+        .rules;
+
+        let problems = config.check_paths();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[0].starts_with("general.input_dir does not exist"));
+        assert!(problems[1].starts_with("rule 'r' schema_file does not exist"));
+        assert!(problems[2].starts_with("rule 'r' move_to is not writable"));
+
+        // A directory that does not exist yet is fine when it can be created
+        config.general.input_dir = dir.path().to_path_buf();
+        config.rules.clear();
+        assert!(config.check_paths().is_empty());
+    }
+
+    #[test]
+    fn decimal_comma_is_checked_across_levels() {
+        let error = parse(
+            "[processing]\ncsv_delimiter = \",\"\n\n\
+             [[rules]]\nname = \"de\"\nglob = \"*.csv\"\n[rules.reader]\ncsv_decimal_comma = true\n",
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("rule 'de'"), "{:#}", error);
+
+        let error = parse(
+            "[processing]\ncsv_decimal_comma = true\n\n\
+             [[rules]]\nglob = \"*.csv\"\n[rules.reader]\ncsv_delimiter = \",\"\n",
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("rule #1"), "{:#}", error);
+
+        assert!(parse("[processing]\ncsv_decimal_comma = true\ncsv_delimiter = \",\"\n").is_err());
+        parse(
+            "[processing]\ncsv_delimiter = \",\"\n\n\
+             [[rules]]\nglob = \"*.csv\"\n[rules.reader]\ncsv_delimiter = \";\"\ncsv_decimal_comma = true\n",
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::{anyhow, Context, Result};
+use toml::{Table, Value};
+
+use super::Config;
+
+/// Variables named `PARSE_TO_PARQUET__<SECTION>__<KEY>` override the matching config key,
+/// e.g. `PARSE_TO_PARQUET__GENERAL__OUTPUT_DIR`. Rules are addressed by index:
+/// `PARSE_TO_PARQUET__RULES__0__OUTPUT_DIR`.
+const ENV_PREFIX: &str = "PARSE_TO_PARQUET__";
+
+/// Applies the overrides from `vars` on top of `config`, in name order.
+pub fn apply_env<I>(config: Config, vars: I) -> Result<Config>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut overrides: Vec<(String, String)> = vars
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect();
+
+    if overrides.is_empty() {
+        return Ok(config);
+    }
+    overrides.sort();
+
+    let mut table = Table::try_from(&config).context("Failed to serialize configuration")?;
+    let mut merged = config;
+
+    for (name, raw) in overrides {
+        let path: Vec<String> = name[ENV_PREFIX.len()..]
+            .split("__")
+            .map(|part| part.to_lowercase())
+            .collect();
+
+        if path.iter().any(|part| part.is_empty()) {
+            return Err(anyhow!("Invalid environment override name: {}", name));
+        }
+
+        // Variables are untyped, so try each reading until the config accepts one
+        let mut error = None;
+        for value in candidates(&raw) {
+            let mut candidate = Value::Table(table.clone());
+            set(&mut candidate, &path, value)
+                .with_context(|| format!("Invalid environment override: {}", name))?;
+
+            match candidate.clone().try_into::<Config>() {
+                Ok(config) => {
+                    if let Value::Table(updated) = candidate {
+                        table = updated;
+                    }
+                    merged = config;
+                    error = None;
+                    break;
+                }
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        if let Some(e) = error {
+            return Err(anyhow!(e).context(format!("Invalid environment override: {}", name)));
+        }
+    }
+
+    Ok(merged)
+}
+
+/// A TOML literal (`5`, `true`, `["csv", "json"]`), the raw string, or a comma-separated list.
+fn candidates(raw: &str) -> Vec<Value> {
+    let mut values = Vec::with_capacity(3);
+
+    if let Ok(parsed) = toml::from_str::<Table>(&format!("value = {}", raw)) {
+        if let Some(value) = parsed.get("value") {
+            values.push(value.clone());
+        }
+    }
+
+    values.push(Value::String(raw.to_string()));
+    values.push(Value::Array(
+        raw.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| Value::String(item.to_string()))
+            .collect(),
+    ));
+
+    values
+}
+
+fn set(target: &mut Value, path: &[String], value: Value) -> Result<()> {
+    let Some((key, rest)) = path.split_first() else {
+        *target = value;
+        return Ok(());
+    };
+
+    match target {
+        Value::Table(table) => {
+            if !table.contains_key(key) {
+                table.insert(key.clone(), Value::Table(Table::new()));
+            }
+            let entry = table.get_mut(key).context("Missing table entry")?;
+            set(entry, rest, value)
+        }
+        Value::Array(items) => {
+            let index: usize = key
+                .parse()
+                .map_err(|_| anyhow!("expected an index, got '{}'", key))?;
+            let len = items.len();
+            let item = items
+                .get_mut(index)
+                .ok_or_else(|| anyhow!("index {} out of range ({} entries)", index, len))?;
+            set(item, rest, value)
+        }
+        _ => Err(anyhow!("'{}' is not a section", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn apply(config: Config, vars: &[(&str, &str)]) -> Result<Config> {
+        apply_env(
+            config,
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        )
+    }
+
+    #[test]
+    fn values_are_parsed_by_the_type_of_their_key() {
+        let config = apply(
+            Config::default(),
+            &[
+                ("PARSE_TO_PARQUET__GENERAL__OUTPUT_DIR", "/data/out"),
+                ("PARSE_TO_PARQUET__GENERAL__RECURSIVE", "true"),
+                ("PARSE_TO_PARQUET__WATCH__POLL_INTERVAL_SECS", "30"),
+                ("PARSE_TO_PARQUET__PROCESSING__CSV_DELIMITER", ";"),
+                ("PARSE_TO_PARQUET__PROCESSING__EXTENSIONS", "csv, tsv"),
+                ("PARSE_TO_PARQUET__SERVE__AUTH_TOKEN", "12345"),
+                ("PATH", "/usr/bin"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.general.output_dir, Path::new("/data/out"));
+        assert!(config.general.recursive);
+        assert_eq!(config.watch.poll_interval_secs, 30);
+        assert_eq!(config.processing.csv_delimiter, Some(';'));
+        assert_eq!(config.processing.extensions, ["csv", "tsv"]);
+        // Read as a string when the key is one, even if it looks like a number
+        assert_eq!(config.serve.auth_token.as_deref(), Some("12345"));
+    }
+
+    #[test]
+    fn rules_are_addressed_by_index() {
+        let config: Config =
+            toml::from_str("[[rules]]\nglob = \"*.csv\"\n[[rules]]\nglob = \"*.xlsx\"\n").unwrap();
+
+        let config = apply(
+            config,
+            &[
+                ("PARSE_TO_PARQUET__RULES__1__OUTPUT_DIR", "xlsx"),
+                ("PARSE_TO_PARQUET__RULES__0__READER__CSV_DELIMITER", "|"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.rules[0].reader.csv_delimiter, Some('|'));
+        assert_eq!(
+            config.rules[1].output_dir.as_deref(),
+            Some(Path::new("xlsx"))
+        );
+
+        let error = apply(config, &[("PARSE_TO_PARQUET__RULES__2__OUTPUT_DIR", "x")]).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("out of range"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[test]
+    fn invalid_overrides_name_the_variable() {
+        for (name, value) in [
+            ("PARSE_TO_PARQUET__GENERAL__NO_SUCH_KEY", "1"),
+            ("PARSE_TO_PARQUET__WATCH__POLL_INTERVAL_SECS", "soon"),
+            ("PARSE_TO_PARQUET__GENERAL____OUTPUT_DIR", "x"),
+            ("PARSE_TO_PARQUET__GENERAL__OUTPUT_DIR__NESTED", "x"),
+        ] {
+            let error = format!(
+                "{:#}",
+                apply(Config::default(), &[(name, value)]).unwrap_err()
+            );
+            assert!(error.contains(name), "{}: {}", name, error);
+        }
+    }
+
+    #[test]
+    fn secrets_from_the_environment_are_masked() {
+        let config = apply(
+            Config::default(),
+            &[
+                ("PARSE_TO_PARQUET__STORAGE__ACCESS_KEY_ID", "AKIDEXAMPLE"),
+                (
+                    "PARSE_TO_PARQUET__STORAGE__SECRET_ACCESS_KEY",
+                    "very-secret",
+                ),
+                ("PARSE_TO_PARQUET__SERVE__AUTH_TOKEN", "token-value"),
+            ],
+        )
+        .unwrap();
+
+        let shown = toml::to_string_pretty(&config.masked()).unwrap();
+        assert!(!shown.contains("very-secret"), "{}", shown);
+        assert!(!shown.contains("token-value"), "{}", shown);
+        assert!(shown.contains("AKIDEXAMPLE"), "{}", shown);
+        assert_eq!(shown.matches("********").count(), 2, "{}", shown);
+        // The config itself keeps the real values
+        assert_eq!(config.serve.auth_token.as_deref(), Some("token-value"));
+    }
+}
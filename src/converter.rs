@@ -1,29 +1,220 @@
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
 
+use crate::config::{LineageConfig, LocaleConfig, QualityConfig, VerifyMode};
 use crate::format::FileFormat;
-use crate::reader::read_file;
-use crate::writer::write_parquet;
+use crate::provenance;
+use crate::quality::{rejects_path, QualityChecks};
+use crate::reader::{read_file_checked, BadLine, BadLinePolicy, ReadOptions};
+use crate::schema::{apply_schema, load_schema, locale_columns};
+use crate::verify::verify_outputs;
+use crate::writer::{write_parquet, write_partitioned};
+
+/// Suffix of the file captured CSV lines are written to, next to the output.
+const BAD_LINES_SUFFIX: &str = "_bad_lines.parquet";
+
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    pub read: ReadOptions,
+    pub schema_file: Option<PathBuf>,
+    pub partition_by: Vec<String>,
+    pub verify: VerifyMode,
+    /// Recorded in the output's metadata, see [`Config::fingerprint`](crate::config::Config::fingerprint)
+    pub config_hash: Option<String>,
+    /// Row-level provenance columns, when enabled
+    pub lineage: Option<LineageConfig>,
+    /// Data quality checks, when any are configured
+    pub quality: Option<QualityConfig>,
+    /// How text is read when `schema_file` types it as numbers or dates
+    pub locale: LocaleConfig,
+}
 
 pub struct ConversionResult {
     pub rows: usize,
     pub columns: usize,
     pub format: FileFormat,
+    pub outputs: Vec<PathBuf>,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    /// Column names and Parquet data types, in order
+    pub schema: Vec<(String, String)>,
+    /// Rows that failed the quality checks, written to `rejects`
+    pub rejected: usize,
+    pub rejects: Option<PathBuf>,
+    /// CSV lines dropped by the bad-line policy; written to `bad_lines` when captured
+    pub skipped_lines: usize,
+    pub bad_lines: Option<PathBuf>,
+}
+
+/// The rows a conversion writes, split by the quality checks.
+pub struct Prepared {
+    pub df: DataFrame,
+    /// Rejected rows with the reason column, when quality checks are configured
+    pub rejects: Option<DataFrame>,
+    /// CSV lines dropped by the bad-line policy
+    pub skipped: Vec<BadLine>,
+}
+
+/// Reads the local file `input` and applies what `options` asks for, in the order a
+/// conversion does: schema and locale casts, lineage columns, then quality checks.
+pub fn prepare(
+    input: &Path,
+    origin: &Path,
+    format: FileFormat,
+    options: &ConvertOptions,
+    converted_at: DateTime<Utc>,
+) -> Result<Prepared> {
+    let schema = options
+        .schema_file
+        .as_deref()
+        .map(load_schema)
+        .transpose()?;
+
+    let mut read = options.read.clone();
+    if let Some(ref schema) = schema {
+        read.text_columns = locale_columns(schema, &options.locale);
+    }
+
+    let (mut df, skipped) = read_file_checked(input, &read)?;
+
+    if let Some(ref schema) = schema {
+        apply_schema(&mut df, schema, &options.locale)?;
+    }
+
+    if let Some(ref lineage) = options.lineage {
+        provenance::append_lineage(
+            &mut df,
+            lineage,
+            input,
+            origin,
+            format,
+            options,
+            converted_at,
+        )?;
+    }
+
+    let rejects = match options.quality {
+        Some(ref quality) => {
+            let checked = QualityChecks::new(quality)?.apply(&df)?;
+            df = checked.valid;
+            Some(checked.rejects)
+        }
+        None => None,
+    };
+
+    Ok(Prepared {
+        df,
+        rejects,
+        skipped,
+    })
 }
 
-pub fn convert(input: &Path, output: &Path, sheet: Option<&str>) -> Result<ConversionResult> {
+/// Converts the local file `input`. `origin` is where it came from, recorded in the
+/// output's metadata; it differs from `input` for files downloaded from S3.
+pub fn convert(
+    input: &Path,
+    origin: &Path,
+    output: &Path,
+    options: &ConvertOptions,
+) -> Result<ConversionResult> {
     let format = FileFormat::from_path(input)
         .ok_or_else(|| anyhow::anyhow!("Unsupported format: {:?}", input.extension()))?;
 
-    let mut df = read_file(input, sheet)?;
+    let converted_at = Utc::now();
+    let Prepared {
+        mut df,
+        rejects: rejected_rows,
+        skipped,
+    } = prepare(input, origin, format, options, converted_at)?;
 
-    let result = ConversionResult {
-        rows: df.height(),
-        columns: df.width(),
-        format,
+    let rows = df.height();
+    let columns = df.width();
+    let schema = df
+        .schema()
+        .iter()
+        .map(|(name, dtype)| (name.to_string(), dtype.to_string()))
+        .collect();
+
+    let metadata = provenance::metadata(input, origin, format, options, converted_at)?;
+
+    let outputs = if options.partition_by.is_empty() {
+        write_parquet(&mut df, output, &metadata)?;
+        vec![output.to_path_buf()]
+    } else {
+        write_partitioned(&df, output, &options.partition_by, &metadata)?
     };
 
-    write_parquet(&mut df, output)?;
+    verify_outputs(&df, &outputs, &options.partition_by, &[], options.verify)?;
+
+    let (rejected, rejects) = match rejected_rows {
+        Some(mut rejected_rows) if rejected_rows.height() > 0 => {
+            let path = rejects_path(output);
+            write_parquet(&mut rejected_rows, &path, &metadata)?;
+            (rejected_rows.height(), Some(path))
+        }
+        _ => (0, None),
+    };
+
+    let bad_lines = if options.read.csv_bad_lines == BadLinePolicy::Capture && !skipped.is_empty() {
+        let path = bad_lines_path(output);
+        write_parquet(&mut bad_lines_frame(&skipped)?, &path, &metadata)?;
+        Some(path)
+    } else {
+        None
+    };
+
+    let input_bytes = fs::metadata(input).map(|m| m.len()).unwrap_or(0);
+    let output_bytes = outputs
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|m| m.len())
+        .sum();
+
+    Ok(ConversionResult {
+        rows,
+        columns,
+        format,
+        outputs,
+        input_bytes,
+        output_bytes,
+        schema,
+        rejected,
+        rejects,
+        skipped_lines: skipped.len(),
+        bad_lines,
+    })
+}
+
+/// `sales.parquet` gives `sales_bad_lines.parquet`, next to the rejects file.
+pub fn bad_lines_path(output: &Path) -> PathBuf {
+    let mut name = output.file_stem().unwrap_or_default().to_os_string();
+    name.push(BAD_LINES_SUFFIX);
+    output.with_file_name(name)
+}
 
-    Ok(result)
+fn bad_lines_frame(bad_lines: &[BadLine]) -> Result<DataFrame> {
+    DataFrame::new(vec![
+        Column::new(
+            "line_number".into(),
+            bad_lines.iter().map(|b| b.line as u64).collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "line".into(),
+            bad_lines
+                .iter()
+                .map(|b| b.content.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "reason".into(),
+            bad_lines
+                .iter()
+                .map(|b| b.reason.as_str())
+                .collect::<Vec<_>>(),
+        ),
+    ])
+    .context("Failed to build bad lines table")
 }
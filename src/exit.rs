@@ -0,0 +1,64 @@
+use std::fmt;
+use std::process::ExitCode;
+
+use crate::processor::ProcessStats;
+
+/// Process exit codes, so schedulers can tell failures apart.
+///
+/// Errors carry one as anyhow context (`.context(Exit::Config)`); `main` looks it up
+/// and falls back to `Failure`. Clap uses 2 for invalid arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Success = 0,
+    Failure = 1,
+    Config = 3,
+    NoInput = 4,
+    SomeFailed = 5,
+    AllFailed = 6,
+    Incomplete = 7,
+}
+
+impl Exit {
+    pub fn for_stats(stats: &ProcessStats) -> Self {
+        if stats.not_attempted > 0 {
+            // Stopped by --fail-fast or a shutdown; the rest may or may not convert
+            Self::Incomplete
+        } else if stats.files.is_empty() {
+            Self::NoInput
+        } else if stats.errors == 0 {
+            Self::Success
+        } else if stats.processed == 0 {
+            Self::AllFailed
+        } else {
+            Self::SomeFailed
+        }
+    }
+
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        error
+            .downcast_ref::<Exit>()
+            .copied()
+            .unwrap_or(Self::Failure)
+    }
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::Success => "Success",
+            Self::Failure => "Failed",
+            Self::Config => "Invalid configuration",
+            Self::NoInput => "No input files",
+            Self::SomeFailed => "Some files failed to convert",
+            Self::AllFailed => "All files failed to convert",
+            Self::Incomplete => "Stopped before all files were tried",
+        };
+        f.write_str(message)
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
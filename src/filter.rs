@@ -0,0 +1,115 @@
+use anyhow::{anyhow, Context, Result};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+use crate::config::ProcessingConfig;
+use crate::format::FileFormat;
+
+/// Decides which files get converted, from the `[processing]` section.
+///
+/// Patterns are matched against the path relative to the input directory.
+/// An `include` entry starting with `!` is treated as an exclude.
+pub struct FileFilter {
+    extensions: Vec<String>,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl FileFilter {
+    pub fn new(config: &ProcessingConfig) -> Result<Self> {
+        let extensions: Vec<String> = config
+            .extensions
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect();
+
+        for ext in &extensions {
+            if !FileFormat::supported_extensions().contains(&ext.as_str()) {
+                return Err(anyhow!(
+                    "Unsupported extension in processing.extensions: {}",
+                    ext
+                ));
+            }
+        }
+
+        let mut include = GlobSetBuilder::new();
+        let mut exclude = GlobSetBuilder::new();
+        let mut has_include = false;
+
+        for pattern in &config.include {
+            match pattern.strip_prefix('!') {
+                Some(negated) => {
+                    exclude.add(compile_glob(negated)?);
+                }
+                None => {
+                    include.add(compile_glob(pattern)?);
+                    has_include = true;
+                }
+            }
+        }
+        for pattern in &config.exclude {
+            exclude.add(compile_glob(pattern.trim_start_matches('!'))?);
+        }
+
+        Ok(Self {
+            extensions,
+            include: if has_include {
+                Some(
+                    include
+                        .build()
+                        .context("Failed to build include patterns")?,
+                )
+            } else {
+                None
+            },
+            exclude: exclude
+                .build()
+                .context("Failed to build exclude patterns")?,
+            min_size: config.min_file_size,
+            max_size: config.max_file_size,
+        })
+    }
+
+    /// `size` is skipped from the checks when unknown.
+    pub fn matches(&self, relative: &Path, size: Option<u64>) -> bool {
+        let ext_allowed = relative
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| {
+                self.extensions
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(e))
+            });
+
+        if !ext_allowed || !FileFormat::is_supported(relative) {
+            return false;
+        }
+
+        if let Some(ref include) = self.include {
+            if !include.is_match(relative) {
+                return false;
+            }
+        }
+
+        if self.exclude.is_match(relative) {
+            return false;
+        }
+
+        match size {
+            Some(size) => {
+                self.min_size.is_none_or(|min| size >= min)
+                    && self.max_size.is_none_or(|max| size <= max)
+            }
+            None => true,
+        }
+    }
+}
+
+pub fn compile_glob(pattern: &str) -> Result<Glob> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .with_context(|| format!("Invalid glob pattern: {}", pattern))
+}
@@ -25,6 +25,18 @@ impl FileFormat {
         }
     }
 
+    /// Lowercase name used in output paths and reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Xml => "xml",
+            Self::Xls => "xls",
+            Self::Xlsx => "xlsx",
+            Self::Ods => "ods",
+        }
+    }
+
     pub fn supported_extensions() -> &'static [&'static str] {
         &["csv", "json", "xml", "xls", "xlsx", "ods"]
     }
@@ -0,0 +1,389 @@
+use anyhow::{anyhow, Context, Result};
+use polars::prelude::*;
+use serde::{Serialize, Serializer};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::OutputFormat;
+use crate::config::Config;
+use crate::format::FileFormat;
+use crate::processor::Planner;
+use crate::reader::{self, Dialect, ReadOptions};
+use crate::schema::{apply_schema, load_schema, locale_columns};
+use crate::storage;
+
+/// Longest cell shown in the sample table; longer values are cut.
+const MAX_CELL_WIDTH: usize = 40;
+
+/// What the readers make of one input: format, layout, inferred schema and a sample.
+#[derive(Debug, Serialize)]
+pub struct Inspection {
+    pub input: PathBuf,
+    pub format: &'static str,
+    pub size: u64,
+    /// The `[[rules]]` entry the file matches below `input_dir`
+    pub rule: Option<String>,
+    pub schema_file: Option<PathBuf>,
+    /// Encoding CSV and XML files are decoded from
+    pub encoding: Option<&'static str>,
+    /// Delimiter, quote, header and decimal comma of CSV files, sniffed unless configured
+    pub dialect: Option<Dialect>,
+    pub sheets: Option<Vec<String>>,
+    pub sheet: Option<String>,
+    pub record_tag: Option<String>,
+    /// Rows read; reading stops after `infer_schema_rows` or the sample, whichever is larger
+    pub rows: usize,
+    /// Whether the file has more rows than were read
+    pub truncated: bool,
+    pub columns: usize,
+    pub schema: Vec<ColumnInfo>,
+    #[serde(serialize_with = "serialize_rows")]
+    pub sample: DataFrame,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    /// Type written to Parquet, after the schema file is applied
+    pub dtype: String,
+    /// Type the reader produced, before the schema file
+    pub read_dtype: String,
+    pub null_count: usize,
+}
+
+/// Reads `input` the way `batch` would, through the rule matching its path below
+/// `input_dir`, and keeps the first `sample_rows` rows. Only as many rows are read as
+/// the sample and schema inference need.
+pub fn inspect(
+    input: &Path,
+    sheet: Option<String>,
+    sample_rows: usize,
+    config: &Config,
+) -> Result<Inspection> {
+    let format = FileFormat::from_path(input)
+        .ok_or_else(|| anyhow!("Unsupported format: {:?}", input.extension()))?;
+
+    let job = Planner::new(config)?.plan(input, &config.general.input_dir, config);
+    let schema = job
+        .options
+        .schema_file
+        .as_deref()
+        .map(load_schema)
+        .transpose()?;
+
+    let mut options = job.options.read.clone();
+    if sheet.is_some() {
+        options.sheet = sheet;
+    }
+    if let Some(ref schema) = schema {
+        options.text_columns = locale_columns(schema, &job.options.locale);
+    }
+    let read_limit = sample_rows.max(options.infer_schema_rows);
+    // One extra row tells a file that ends at the limit from a longer one
+    options.max_rows = Some(read_limit + 1);
+
+    let staged = storage::stage_input(input, &config.storage)?;
+    let local = staged.path();
+
+    let size = fs::metadata(local)
+        .with_context(|| format!("Failed to read {}", input.display()))?
+        .len();
+
+    let layout = layout(local, format, &options)?;
+    let encoding = reader::text_encoding(local, &options)?;
+    let dialect = match format {
+        FileFormat::Csv => Some(reader::csv_dialect(local, &options)?),
+        _ => None,
+    };
+
+    let df = reader::read_file(local, &options)
+        .with_context(|| format!("Failed to read {}", input.display()))?;
+    let truncated = df.height() > read_limit;
+    let mut df = df.head(Some(read_limit));
+
+    let read_schema = df.schema().clone();
+    if let Some(ref schema) = schema {
+        apply_schema(&mut df, schema, &job.options.locale)
+            .with_context(|| format!("Failed to apply schema to {}", input.display()))?;
+    }
+
+    let columns = df
+        .get_columns()
+        .iter()
+        .map(|column| ColumnInfo {
+            name: column.name().to_string(),
+            dtype: column.dtype().to_string(),
+            read_dtype: read_schema
+                .get(column.name())
+                .unwrap_or(column.dtype())
+                .to_string(),
+            null_count: column.null_count(),
+        })
+        .collect();
+
+    Ok(Inspection {
+        input: input.to_path_buf(),
+        format: format.name(),
+        size,
+        rule: job.rule,
+        schema_file: job.options.schema_file,
+        encoding,
+        dialect,
+        sheets: layout.sheets,
+        sheet: layout.sheet,
+        record_tag: layout.record_tag,
+        rows: df.height(),
+        truncated,
+        columns: df.width(),
+        schema: columns,
+        sample: df.head(Some(sample_rows)),
+    })
+}
+
+/// Sheet list and selected sheet for workbooks, record tag for XML.
+#[derive(Default)]
+struct Layout {
+    sheets: Option<Vec<String>>,
+    sheet: Option<String>,
+    record_tag: Option<String>,
+}
+
+fn layout(path: &Path, format: FileFormat, options: &ReadOptions) -> Result<Layout> {
+    match format {
+        FileFormat::Xls | FileFormat::Xlsx | FileFormat::Ods => {
+            let sheets = reader::sheet_names(path)?;
+            Ok(Layout {
+                sheet: options.sheet.clone().or_else(|| sheets.first().cloned()),
+                sheets: Some(sheets),
+                record_tag: None,
+            })
+        }
+        FileFormat::Xml => Ok(Layout {
+            record_tag: reader::xml_record_tag(path, options)?,
+            ..Default::default()
+        }),
+        _ => Ok(Layout::default()),
+    }
+}
+
+pub fn print_inspection(inspection: &Inspection, format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        let json =
+            serde_json::to_string_pretty(inspection).context("Failed to serialize inspection")?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!("{}", inspection.input.display());
+    println!(
+        "  format: {}  size: {} bytes",
+        inspection.format, inspection.size
+    );
+    if let Some(ref rule) = inspection.rule {
+        println!("  rule: {}", rule);
+    }
+    if let Some(ref schema_file) = inspection.schema_file {
+        println!("  schema file: {}", schema_file.display());
+    }
+    if let Some(encoding) = inspection.encoding {
+        println!("  encoding: {}", encoding);
+    }
+    if let Some(ref dialect) = inspection.dialect {
+        println!(
+            "  delimiter: {}  quote: {}  header: {}  decimal comma: {}",
+            (dialect.delimiter as char).escape_default(),
+            dialect.quote_char as char,
+            yes_no(dialect.has_header),
+            yes_no(dialect.decimal_comma)
+        );
+    }
+    if let Some(ref sheets) = inspection.sheets {
+        println!("  sheets: {}", sheets.join(", "));
+    }
+    if let Some(ref sheet) = inspection.sheet {
+        println!("  sheet: {}", sheet);
+    }
+    if let Some(ref tag) = inspection.record_tag {
+        println!("  record tag: {}", tag);
+    }
+    if inspection.truncated {
+        println!(
+            "  rows: {}+ (stopped reading there)  columns: {}",
+            inspection.rows, inspection.columns
+        );
+    } else {
+        println!(
+            "  rows: {}  columns: {}",
+            inspection.rows, inspection.columns
+        );
+    }
+
+    println!();
+    println!("Schema:");
+    let width = inspection
+        .schema
+        .iter()
+        .map(|c| c.name.len())
+        .max()
+        .unwrap_or(0);
+    for column in &inspection.schema {
+        let read_as = if column.read_dtype == column.dtype {
+            String::new()
+        } else {
+            format!("  (read as {})", column.read_dtype)
+        };
+        println!(
+            "  {:<width$}  {:<10}  nulls: {}{}",
+            column.name,
+            column.dtype,
+            column.null_count,
+            read_as,
+            width = width
+        );
+    }
+
+    println!();
+    println!("First {} row(s):", inspection.sample.height());
+    print_table(&inspection.sample);
+
+    Ok(())
+}
+
+/// Prints every row and column of `df` as an aligned text table.
+fn print_table(df: &DataFrame) {
+    let header: Vec<String> = df
+        .get_column_names()
+        .iter()
+        .map(|name| cell(name.as_str()))
+        .collect();
+
+    let rows: Vec<Vec<String>> = (0..df.height())
+        .map(|row| {
+            df.get_columns()
+                .iter()
+                .map(|column| match column.get(row) {
+                    Ok(AnyValue::Null) | Err(_) => "null".to_string(),
+                    Ok(AnyValue::String(value)) => cell(value),
+                    Ok(value) => cell(&value.to_string()),
+                })
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header[i].chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let print_row = |cells: &[String]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("  {}", line.join(" | ").trim_end());
+    };
+
+    print_row(&header);
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    println!("  {}", rule.join("-+-"));
+    for row in &rows {
+        print_row(row);
+    }
+}
+
+/// One table cell on a single line, cut to `MAX_CELL_WIDTH` characters.
+fn cell(value: &str) -> String {
+    let value = value.replace(['\n', '\r', '\t'], " ");
+    if value.chars().count() <= MAX_CELL_WIDTH {
+        return value;
+    }
+
+    let mut cut: String = value.chars().take(MAX_CELL_WIDTH - 1).collect();
+    cut.push('…');
+    cut
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn serialize_rows<S: Serializer>(df: &DataFrame, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut buf = Vec::new();
+    JsonWriter::new(&mut buf)
+        .with_json_format(JsonFormat::Json)
+        .finish(&mut df.clone())
+        .map_err(serde::ser::Error::custom)?;
+
+    let rows: serde_json::Value =
+        serde_json::from_slice(&buf).map_err(serde::ser::Error::custom)?;
+    rows.serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_inspected_through_their_rule_and_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("in")).unwrap();
+        fs::write(
+            root.join("in/sales.csv"),
+            "id;amount\n1;1.234,5\n2;7\n3;8\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("schema.toml"),
+            "[columns]\namount = { type = \"f64\", decimal_separator = \",\", thousands_separator = \".\" }\n",
+        )
+        .unwrap();
+
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [general]
+            input_dir = "{root}/in"
+
+            [[rules]]
+            name = "sales"
+            glob = "*.csv"
+            schema_file = "{root}/schema.toml"
+            [rules.reader]
+            csv_delimiter = ";"
+            "#,
+            root = root.display(),
+        ))
+        .unwrap();
+
+        let inspection = inspect(&root.join("in/sales.csv"), None, 2, &config).unwrap();
+
+        assert_eq!(inspection.rule.as_deref(), Some("sales"));
+        assert_eq!(inspection.dialect.unwrap().delimiter, b';');
+        let amount = &inspection.schema[1];
+        assert_eq!(
+            (amount.dtype.as_str(), amount.read_dtype.as_str()),
+            ("f64", "str")
+        );
+        assert_eq!(inspection.sample.height(), 2);
+        assert_eq!(
+            inspection
+                .sample
+                .column("amount")
+                .unwrap()
+                .f64()
+                .unwrap()
+                .get(0),
+            Some(1234.5)
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::{anyhow, Result};
+use std::io::{self, IsTerminal, Write};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogFormat, LoggingConfig};
+use crate::progress;
+
+const LEVELS: [LevelFilter; 6] = [
+    LevelFilter::OFF,
+    LevelFilter::ERROR,
+    LevelFilter::WARN,
+    LevelFilter::INFO,
+    LevelFilter::DEBUG,
+    LevelFilter::TRACE,
+];
+
+pub fn parse_level(level: &str) -> Result<LevelFilter> {
+    level.parse().map_err(|_| {
+        anyhow!(
+            "Invalid logging.level '{}', expected off, error, warn, info, debug or trace",
+            level
+        )
+    })
+}
+
+/// Installs the global subscriber, writing to stderr. `verbosity` is the number of
+/// `-v` flags minus the number of `-q` flags. `RUST_LOG` replaces the level when set.
+pub fn init(config: &LoggingConfig, verbosity: i8) -> Result<()> {
+    let level = adjust(parse_level(&config.level)?, verbosity);
+
+    // Dependencies stay at warnings so -v shows this tool's debug output, not HTTP internals
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(directives) if !directives.is_empty() => EnvFilter::new(directives),
+        _ => EnvFilter::new(format!(
+            "{},{}={}",
+            level.min(LevelFilter::WARN),
+            env!("CARGO_CRATE_NAME"),
+            level
+        )),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(|| LogWriter)
+        .with_ansi(io::stderr().is_terminal())
+        .with_target(false);
+
+    let result = match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .try_init(),
+    };
+
+    result.map_err(|e| anyhow!("Failed to initialize logging: {}", e))
+}
+
+fn adjust(level: LevelFilter, verbosity: i8) -> LevelFilter {
+    let index = LEVELS.iter().position(|l| *l == level).unwrap_or(3) as i8;
+    LEVELS[(index + verbosity).clamp(0, LEVELS.len() as i8 - 1) as usize]
+}
+
+/// Writes to stderr, above the progress bar when one is shown.
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        progress::suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
@@ -1,31 +1,93 @@
 mod cli;
 mod config;
 mod converter;
+mod exit;
+mod filter;
 mod format;
+mod inspect;
+mod logging;
+mod metrics;
+mod parquet_info;
+mod plan;
 mod processor;
+mod progress;
+mod provenance;
+mod quality;
+mod report;
 mod reader;
+mod rules;
+mod schema;
+mod server;
+mod shutdown;
+mod storage;
+mod template;
+mod verify;
 mod watcher;
 mod writer;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::Parser;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+use tracing::{error, info, warn};
 
-use cli::{Cli, Commands};
-use config::Config;
-use converter::convert;
-use processor::process_batch;
+use cli::{Cli, Commands, ConfigAction};
+use config::{Config, VerifyMode};
+use exit::Exit;
+use converter::{ConversionResult, ConvertOptions};
+use processor::{
+    convert_file, display_paths, locale_config, process_batch, quality_config, read_options,
+    Planner, ProcessStats,
+};
+use report::FileReport;
+use server::serve;
 use watcher::watch;
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
+    match run(cli) {
+        Ok(exit) => exit.into(),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            Exit::from_error(&e).into()
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<Exit> {
+    let verbosity = cli.verbosity();
+
     match cli.command {
-        Commands::Convert { input, output, sheet } => {
-            run_convert(input, output, sheet)
+        Commands::Convert { input, output, sheet, report, output_format } => {
+            let config = load_config(cli.config.as_deref())?;
+            logging::init(&config.logging, verbosity)?;
+
+            let start = Instant::now();
+            let mut stats = ProcessStats::default();
+            let result = run_convert(&input, output, sheet, &config);
+
+            stats.record(match result {
+                Ok(ref converted) => FileReport::converted(&input, converted, start.elapsed()),
+                Err(ref e) => FileReport::failed(&input, e, start.elapsed()),
+            });
+            stats.elapsed = start.elapsed();
+
+            report::emit(&stats, report.as_deref(), output_format)?;
+            result.map(|_| Exit::Success)
         }
 
-        Commands::Batch { input_dir, output_dir, delete_source, recursive } => {
+        Commands::Batch {
+            input_dir,
+            output_dir,
+            delete_source,
+            recursive,
+            report,
+            output_format,
+            fail_fast,
+            dry_run,
+        } => {
             let config = load_config_with_overrides(
                 cli.config.as_deref(),
                 input_dir,
@@ -33,69 +95,267 @@ fn main() -> Result<()> {
                 delete_source,
                 recursive,
             )?;
-            process_batch(&config)?;
-            Ok(())
+            logging::init(&config.logging, verbosity)?;
+
+            if dry_run {
+                let planned = plan::plan_batch(&config)?;
+                plan::print_plan(&planned, report.as_deref(), output_format)?;
+                return Ok(if planned.is_empty() { Exit::NoInput } else { Exit::Success });
+            }
+
+            shutdown::install()?;
+            let stats = process_batch(&config, fail_fast)?;
+            report::emit(&stats, report.as_deref(), output_format)?;
+
+            let exit = Exit::for_stats(&stats);
+            if exit == Exit::NoInput {
+                warn!(input_dir = %config.general.input_dir.display(), "No input files found");
+            }
+            Ok(exit)
         }
 
-        Commands::Watch { input_dir, output_dir, delete_source, recursive } => {
-            let config = load_config_with_overrides(
-                cli.config.as_deref(),
-                input_dir,
-                output_dir,
-                delete_source,
-                recursive,
-            )?;
-            watch(&config)
+        Commands::Watch {
+            input_dir,
+            output_dir,
+            delete_source,
+            recursive,
+            metrics_listen,
+        } => {
+            // Re-run on SIGHUP so CLI flags keep precedence over the reloaded file
+            let load = || {
+                let mut config = load_config_with_overrides(
+                    cli.config.as_deref(),
+                    input_dir.clone(),
+                    output_dir.clone(),
+                    delete_source,
+                    recursive,
+                )?;
+                if metrics_listen.is_some() {
+                    config.metrics.listen = metrics_listen.clone();
+                }
+                Ok(config)
+            };
+            let config = load()?;
+            logging::init(&config.logging, verbosity)?;
+            shutdown::install()?;
+            watch(config, load)?;
+            Ok(Exit::Success)
+        }
+
+        Commands::Serve { listen, output_dir, delete_source } => {
+            let load = || {
+                let mut config = Config::load(cli.config.as_deref()).context(Exit::Config)?;
+                config.general.apply_overrides(None, output_dir.clone(), delete_source, false);
+                if let Some(ref addr) = listen {
+                    config.serve.listen = addr.clone();
+                }
+                config.validate().context(Exit::Config)?;
+                server::check_config(&config).context(Exit::Config)?;
+                Ok(config)
+            };
+            let config = load()?;
+            logging::init(&config.logging, verbosity)?;
+            shutdown::install()?;
+            serve(config, load)?;
+            Ok(Exit::Success)
+        }
+
+        Commands::Inspect { input, sheet, rows, output_format } => {
+            let config = load_config(cli.config.as_deref())?;
+            logging::init(&config.logging, verbosity)?;
+
+            if !storage::is_remote(&input) && !input.exists() {
+                return Err(anyhow!("File not found: {}", input.display()).context(Exit::NoInput));
+            }
+
+            let inspection = inspect::inspect(&input, sheet, rows, &config)?;
+            inspect::print_inspection(&inspection, output_format)?;
+            Ok(Exit::Success)
+        }
+
+        Commands::ParquetInfo { input, output_format } => {
+            let config = load_config(cli.config.as_deref())?;
+            logging::init(&config.logging, verbosity)?;
+
+            for path in &input {
+                if !storage::is_remote(path) && !path.exists() {
+                    return Err(anyhow!("File not found: {}", path.display()).context(Exit::NoInput));
+                }
+            }
+
+            let infos = parquet_info::collect_files(&input)?
+                .iter()
+                .map(|path| parquet_info::read_info(path, &config.storage))
+                .collect::<Result<Vec<_>>>()?;
+            parquet_info::print_info(&infos, output_format)?;
+            Ok(Exit::Success)
+        }
+
+        Commands::Verify { input, output, sheet, checksum } => {
+            let config = load_config(cli.config.as_deref())?;
+            logging::init(&config.logging, verbosity)?;
+
+            run_verify(&input, output, sheet, checksum, &config)?;
+            Ok(Exit::Success)
+        }
+
+        Commands::ValidateConfig => {
+            validate_config(cli.config.as_deref(), verbosity)?;
+            Ok(Exit::Success)
+        }
+
+        Commands::Config { action: ConfigAction::Show } => {
+            let config = Config::load(cli.config.as_deref()).context(Exit::Config)?;
+            let content = toml::to_string_pretty(&config.masked())?;
+            print!("{}", content);
+            Ok(Exit::Success)
         }
 
         Commands::InitConfig { output } => {
             Config::generate_default(&output)?;
             println!("Configuration file created: {}", output.display());
-            Ok(())
+            Ok(Exit::Success)
         }
     }
 }
 
-fn run_convert(input: PathBuf, output: Option<PathBuf>, sheet: Option<String>) -> Result<()> {
-    if !input.exists() {
-        return Err(anyhow!("File not found: {}", input.display()));
+fn run_convert(
+    input: &Path,
+    output: Option<PathBuf>,
+    sheet: Option<String>,
+    config: &Config,
+) -> Result<ConversionResult> {
+    if !storage::is_remote(input) && !input.exists() {
+        return Err(anyhow!("File not found: {}", input.display()).context(Exit::NoInput));
     }
 
-    let output = output.unwrap_or_else(|| {
-        let mut out = input.clone();
-        out.set_extension("parquet");
-        out
-    });
+    let output = output.unwrap_or_else(|| input.with_extension("parquet"));
 
-    println!("[CONVERTING] {}", input.display());
+    let start = Instant::now();
+    if config.logging.show_progress {
+        info!(input = %input.display(), "Converting");
+    }
+
+    let mut options = ConvertOptions {
+        read: read_options(config, None),
+        verify: config.processing.verify,
+        config_hash: Some(config.fingerprint()),
+        lineage: config.lineage.enabled.then(|| config.lineage.clone()),
+        quality: quality_config(config, None),
+        locale: locale_config(config, None),
+        ..Default::default()
+    };
+    if sheet.is_some() {
+        options.read.sheet = sheet;
+    }
 
-    let result = convert(&input, &output, sheet.as_deref())?;
+    let result =
+        convert_file(input, &output, &options, &config.storage).context(Exit::AllFailed)?;
 
-    println!(
-        "  Format: {:?} | Rows: {} | Columns: {}",
-        result.format, result.rows, result.columns
+    info!(
+        input = %input.display(),
+        output = %display_paths(&result.outputs),
+        format = ?result.format,
+        rows = result.rows,
+        rejected = result.rejected,
+        skipped_lines = result.skipped_lines,
+        columns = result.columns,
+        bytes_in = result.input_bytes,
+        bytes_out = result.output_bytes,
+        duration_ms = start.elapsed().as_millis() as u64,
+        "Converted"
+    );
+
+    Ok(result)
+}
+
+fn run_verify(
+    input: &Path,
+    output: Option<PathBuf>,
+    sheet: Option<String>,
+    checksum: bool,
+    config: &Config,
+) -> Result<()> {
+    let output = output.unwrap_or_else(|| input.with_extension("parquet"));
+
+    for path in [input, output.as_path()] {
+        if !storage::is_remote(path) && !path.exists() {
+            return Err(anyhow!("File not found: {}", path.display()).context(Exit::NoInput));
+        }
+    }
+
+    // Read the source the way batch converts it, through the matching rule
+    let planner = Planner::new(config)?;
+    let mut job = planner.plan(input, &config.general.input_dir, config);
+    if sheet.is_some() {
+        job.options.read.sheet = sheet;
+    }
+
+    let mode = if checksum {
+        VerifyMode::Checksum
+    } else {
+        VerifyMode::Basic
+    };
+
+    let (rows, columns) =
+        verify::verify_file(input, &output, &job.options, mode, &config.storage)?;
+
+    info!(
+        input = %input.display(),
+        output = %output.display(),
+        rows,
+        columns,
+        checksum,
+        "Verified"
     );
-    println!("[OK] {}", output.display());
 
     Ok(())
 }
 
+fn validate_config(config_path: Option<&Path>, verbosity: i8) -> Result<()> {
+    let config = load_config(config_path)?;
+    logging::init(&config.logging, verbosity)?;
+
+    let problems = config.check_paths();
+    for problem in &problems {
+        error!("{}", problem);
+    }
+
+    if !problems.is_empty() {
+        return Err(anyhow!("{} problem(s) found in configuration", problems.len()))
+            .context(Exit::Config);
+    }
+
+    info!("Configuration is valid");
+    Ok(())
+}
+
+/// Loads and validates the configuration, marking failures as config errors.
+fn load_config(config_path: Option<&Path>) -> Result<Config> {
+    let config = Config::load(config_path).context(Exit::Config)?;
+    config.validate().context(Exit::Config)?;
+    Ok(config)
+}
+
 fn load_config_with_overrides(
-    config_path: Option<&std::path::Path>,
+    config_path: Option<&Path>,
     input_dir: Option<PathBuf>,
     output_dir: Option<PathBuf>,
     delete_source: bool,
     recursive: bool,
 ) -> Result<Config> {
-    let mut config = Config::load(config_path)?;
+    let mut config = Config::load(config_path).context(Exit::Config)?;
 
     config.general.apply_overrides(input_dir, output_dir, delete_source, recursive);
 
-    if !config.general.input_dir.exists() {
+    config.validate().context(Exit::Config)?;
+
+    if !storage::is_remote(&config.general.input_dir) && !config.general.input_dir.exists() {
         return Err(anyhow!(
             "Directory not found: {}",
             config.general.input_dir.display()
-        ));
+        ))
+        .context(Exit::Config);
     }
 
     Ok(config)
@@ -0,0 +1,229 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tiny_http::{Header, Method, Response, Server};
+use tracing::{error, info};
+
+use crate::format::FileFormat;
+
+const DURATION_BUCKETS: &[f64] = &[
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+const SIZE_BUCKETS: &[f64] = &[
+    1024.0,
+    10240.0,
+    102400.0,
+    1048576.0,
+    10485760.0,
+    104857600.0,
+    1073741824.0,
+    10737418240.0,
+];
+
+/// Conversion counters for the whole process. Recorded by every mode, exposed only
+/// when the watch metrics endpoint is enabled.
+static METRICS: LazyLock<Mutex<Metrics>> = LazyLock::new(|| Mutex::new(Metrics::new()));
+
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+struct Metrics {
+    converted: BTreeMap<&'static str, u64>,
+    failed: BTreeMap<&'static str, u64>,
+    rows: u64,
+    duration: Histogram,
+    input_size: Histogram,
+    queue_depth: usize,
+    last_success: Option<f64>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            converted: BTreeMap::new(),
+            failed: BTreeMap::new(),
+            rows: 0,
+            duration: Histogram::new(DURATION_BUCKETS),
+            input_size: Histogram::new(SIZE_BUCKETS),
+            queue_depth: 0,
+            last_success: None,
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+
+        render_counter(
+            &mut out,
+            "parse_to_parquet_files_converted_total",
+            "Files converted successfully, by source format.",
+            &self.converted,
+        );
+        render_counter(
+            &mut out,
+            "parse_to_parquet_files_failed_total",
+            "Files that failed to convert, by source format.",
+            &self.failed,
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP parse_to_parquet_rows_written_total Rows written to Parquet."
+        );
+        let _ = writeln!(out, "# TYPE parse_to_parquet_rows_written_total counter");
+        let _ = writeln!(out, "parse_to_parquet_rows_written_total {}", self.rows);
+
+        self.duration.render(
+            &mut out,
+            "parse_to_parquet_conversion_duration_seconds",
+            "Time to convert one file.",
+        );
+        self.input_size.render(
+            &mut out,
+            "parse_to_parquet_input_size_bytes",
+            "Size of converted source files.",
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP parse_to_parquet_queue_depth Files found and waiting to be converted."
+        );
+        let _ = writeln!(out, "# TYPE parse_to_parquet_queue_depth gauge");
+        let _ = writeln!(out, "parse_to_parquet_queue_depth {}", self.queue_depth);
+
+        if let Some(timestamp) = self.last_success {
+            let _ = writeln!(
+                out,
+                "# HELP parse_to_parquet_last_success_timestamp_seconds Unix time of the last successful conversion."
+            );
+            let _ = writeln!(
+                out,
+                "# TYPE parse_to_parquet_last_success_timestamp_seconds gauge"
+            );
+            let _ = writeln!(
+                out,
+                "parse_to_parquet_last_success_timestamp_seconds {}",
+                timestamp
+            );
+        }
+
+        out
+    }
+}
+
+fn render_counter(out: &mut String, name: &str, help: &str, values: &BTreeMap<&'static str, u64>) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for (format, value) in values {
+        let _ = writeln!(out, "{}{{format=\"{}\"}} {}", name, format, value);
+    }
+}
+
+fn with_metrics(f: impl FnOnce(&mut Metrics)) {
+    if let Ok(mut metrics) = METRICS.lock() {
+        f(&mut metrics);
+    }
+}
+
+pub fn record_success(format: FileFormat, rows: usize, input_bytes: u64, duration: Duration) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default();
+
+    with_metrics(|m| {
+        *m.converted.entry(format.name()).or_default() += 1;
+        m.rows += rows as u64;
+        m.duration.observe(duration.as_secs_f64());
+        m.input_size.observe(input_bytes as f64);
+        m.last_success = Some(now);
+    });
+}
+
+pub fn record_failure(format: Option<FileFormat>, duration: Duration) {
+    with_metrics(|m| {
+        *m.failed
+            .entry(format.map_or("unknown", |f| f.name()))
+            .or_default() += 1;
+        m.duration.observe(duration.as_secs_f64());
+    });
+}
+
+pub fn set_queue_depth(depth: usize) {
+    with_metrics(|m| m.queue_depth = depth);
+}
+
+/// Serves `/metrics` and `/healthz` on a background thread for the life of the process.
+pub fn serve(listen: &str) -> Result<()> {
+    let server = Server::http(listen)
+        .map_err(|e| anyhow!("Failed to listen for metrics on {}: {}", listen, e))?;
+
+    info!(listen = %listen, "Serving metrics");
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            // Scrapers may add a query string, e.g. /metrics?format=prometheus
+            let path = request.url().split('?').next().unwrap_or_default();
+            let (status, content_type, body) = match (request.method(), path) {
+                (Method::Get, "/metrics") => {
+                    let body = METRICS.lock().map(|m| m.render()).unwrap_or_default();
+                    (200, "text/plain; version=0.0.4; charset=utf-8", body)
+                }
+                (Method::Get, "/healthz") => (200, "text/plain; charset=utf-8", "ok\n".to_string()),
+                _ => (404, "text/plain; charset=utf-8", "Not found\n".to_string()),
+            };
+
+            let header =
+                Header::from_bytes("Content-Type", content_type).expect("static header is valid");
+            let response = Response::from_string(body)
+                .with_status_code(status)
+                .with_header(header);
+
+            if let Err(e) = request.respond(response) {
+                error!(error = %e, "Failed to send metrics response");
+            }
+        }
+    });
+
+    Ok(())
+}
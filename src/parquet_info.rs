@@ -0,0 +1,260 @@
+use anyhow::{anyhow, Context, Result};
+use polars::prelude::*;
+use polars_parquet::parquet::metadata::{ColumnChunkMetadata, FileMetadata};
+use polars_parquet::parquet::statistics::Statistics;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::cli::OutputFormat;
+use crate::config::StorageConfig;
+use crate::report::ColumnReport;
+use crate::storage;
+
+/// Key-value values longer than this are summarized in text output (the embedded
+/// Arrow schema is a long base64 blob).
+const MAX_TEXT_VALUE: usize = 80;
+
+/// Footer metadata of one Parquet file.
+#[derive(Debug, Serialize)]
+pub struct ParquetInfo {
+    pub path: PathBuf,
+    pub size: u64,
+    pub rows: usize,
+    pub created_by: Option<String>,
+    pub schema: Vec<ColumnReport>,
+    pub row_groups: Vec<RowGroupInfo>,
+    pub key_value: BTreeMap<String, Option<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowGroupInfo {
+    pub rows: usize,
+    pub compressed_bytes: u64,
+    pub uncompressed_bytes: u64,
+    pub columns: Vec<ColumnChunkInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColumnChunkInfo {
+    pub name: String,
+    pub codec: String,
+    pub compressed_bytes: u64,
+    pub uncompressed_bytes: u64,
+    pub null_count: Option<i64>,
+    pub min: Option<String>,
+    pub max: Option<String>,
+}
+
+/// Expands directories, such as a partitioned dataset, to the Parquet files below them.
+pub fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if storage::is_remote(path) || !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+
+        let mut found: Vec<PathBuf> = WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path())
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("parquet"))
+            })
+            .collect();
+
+        if found.is_empty() {
+            return Err(anyhow!("No Parquet files in: {}", path.display()));
+        }
+
+        found.sort();
+        files.extend(found);
+    }
+
+    Ok(files)
+}
+
+pub fn read_info(path: &Path, storage_config: &StorageConfig) -> Result<ParquetInfo> {
+    let staged = storage::stage_input(path, storage_config)?;
+    let local = staged.path();
+
+    let size = fs::metadata(local)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .len();
+
+    let file = File::open(local).context("Failed to open Parquet file")?;
+    let mut reader = ParquetReader::new(file);
+
+    let arrow_schema = reader
+        .schema()
+        .with_context(|| format!("Failed to read Parquet schema: {}", path.display()))?;
+    let schema = Schema::from_arrow_schema(&arrow_schema)
+        .iter()
+        .map(|(name, dtype)| ColumnReport {
+            name: name.to_string(),
+            dtype: dtype.to_string(),
+        })
+        .collect();
+
+    let metadata = reader
+        .get_metadata()
+        .with_context(|| format!("Failed to read Parquet metadata: {}", path.display()))?;
+
+    Ok(ParquetInfo {
+        path: path.to_path_buf(),
+        size,
+        rows: metadata.num_rows,
+        created_by: metadata.created_by.clone(),
+        schema,
+        row_groups: row_groups(metadata),
+        key_value: metadata
+            .key_value_metadata()
+            .iter()
+            .flatten()
+            .map(|kv| (kv.key.clone(), kv.value.clone()))
+            .collect(),
+    })
+}
+
+fn row_groups(metadata: &FileMetadata) -> Vec<RowGroupInfo> {
+    metadata
+        .row_groups
+        .iter()
+        .map(|group| {
+            let columns: Vec<ColumnChunkInfo> = metadata
+                .schema()
+                .fields()
+                .iter()
+                .filter_map(|field| group.columns_under_root_iter(field.name()))
+                .flatten()
+                .map(column_chunk)
+                .collect();
+
+            RowGroupInfo {
+                rows: group.num_rows(),
+                compressed_bytes: group.compressed_size() as u64,
+                uncompressed_bytes: group.total_byte_size() as u64,
+                columns,
+            }
+        })
+        .collect()
+}
+
+fn column_chunk(column: &ColumnChunkMetadata) -> ColumnChunkInfo {
+    let stats = column.statistics().and_then(|s| s.ok());
+    let (null_count, min, max) = match stats {
+        Some(ref stats) => statistics(stats),
+        None => (None, None, None),
+    };
+
+    ColumnChunkInfo {
+        name: column.descriptor().path_in_schema.join("."),
+        codec: format!("{:?}", column.compression()).to_lowercase(),
+        compressed_bytes: column.compressed_size() as u64,
+        uncompressed_bytes: column.uncompressed_size() as u64,
+        null_count,
+        min,
+        max,
+    }
+}
+
+/// Null count, min and max as written. Values are shown by physical type, so dates
+/// and timestamps appear as their integer encoding.
+fn statistics(stats: &Statistics) -> (Option<i64>, Option<String>, Option<String>) {
+    fn text(value: &Option<Vec<u8>>) -> Option<String> {
+        value
+            .as_ref()
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+    }
+
+    fn plain<T: ToString>(value: &Option<T>) -> Option<String> {
+        value.as_ref().map(T::to_string)
+    }
+
+    match stats {
+        Statistics::Binary(s) => (s.null_count, text(&s.min_value), text(&s.max_value)),
+        Statistics::FixedLen(s) => (s.null_count, text(&s.min_value), text(&s.max_value)),
+        Statistics::Boolean(s) => (s.null_count, plain(&s.min_value), plain(&s.max_value)),
+        Statistics::Int32(s) => (s.null_count, plain(&s.min_value), plain(&s.max_value)),
+        Statistics::Int64(s) => (s.null_count, plain(&s.min_value), plain(&s.max_value)),
+        Statistics::Float(s) => (s.null_count, plain(&s.min_value), plain(&s.max_value)),
+        Statistics::Double(s) => (s.null_count, plain(&s.min_value), plain(&s.max_value)),
+        Statistics::Int96(s) => (
+            s.null_count,
+            s.min_value.map(|v| format!("{:?}", v)),
+            s.max_value.map(|v| format!("{:?}", v)),
+        ),
+    }
+}
+
+pub fn print_info(infos: &[ParquetInfo], format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        let json = serde_json::to_string_pretty(infos).context("Failed to serialize metadata")?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    for (index, info) in infos.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+
+        println!("{}", info.path.display());
+        println!(
+            "  size: {} bytes  rows: {}  row groups: {}",
+            info.size,
+            info.rows,
+            info.row_groups.len()
+        );
+        if let Some(ref created_by) = info.created_by {
+            println!("  created by: {}", created_by);
+        }
+
+        println!("  schema:");
+        for column in &info.schema {
+            println!("    {}: {}", column.name, column.dtype);
+        }
+
+        for (index, group) in info.row_groups.iter().enumerate() {
+            println!(
+                "  row group {}: {} rows, {} bytes compressed, {} bytes uncompressed",
+                index, group.rows, group.compressed_bytes, group.uncompressed_bytes
+            );
+            for column in &group.columns {
+                println!(
+                    "    {}: {}, {} -> {} bytes, nulls: {}, min: {}, max: {}",
+                    column.name,
+                    column.codec,
+                    column.uncompressed_bytes,
+                    column.compressed_bytes,
+                    column
+                        .null_count
+                        .map_or_else(|| "-".to_string(), |n| n.to_string()),
+                    column.min.as_deref().unwrap_or("-"),
+                    column.max.as_deref().unwrap_or("-")
+                );
+            }
+        }
+
+        if !info.key_value.is_empty() {
+            println!("  metadata:");
+            for (key, value) in &info.key_value {
+                match value {
+                    Some(value) if value.len() > MAX_TEXT_VALUE => {
+                        println!("    {}: ({} bytes)", key, value.len())
+                    }
+                    Some(value) => println!("    {}: {}", key, value),
+                    None => println!("    {}", key),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
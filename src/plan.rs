@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::OutputFormat;
+use crate::config::{Config, PostAction};
+use crate::format::FileFormat;
+use crate::processor::{collect_inputs, Planner};
+
+/// What `batch` would do with one input, resolved without reading it.
+#[derive(Debug, Serialize)]
+pub struct PlannedFile {
+    pub input: PathBuf,
+    pub size: u64,
+    pub format: Option<&'static str>,
+    pub rule: Option<String>,
+    pub sheet: Option<String>,
+    pub output: PathBuf,
+    pub partition_by: Vec<String>,
+    pub post_action: PostAction,
+    pub move_to: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct Plan<'a> {
+    dry_run: bool,
+    files: &'a [PlannedFile],
+}
+
+/// Lists the inputs `batch` would convert, after filters and routing rules.
+pub fn plan_batch(config: &Config) -> Result<Vec<PlannedFile>> {
+    let planner = Planner::new(config)?;
+
+    collect_inputs(config)?
+        .into_iter()
+        .map(|(path, size)| {
+            let job = planner.plan(&path, &config.general.input_dir, config);
+            let format = FileFormat::from_path(&job.relative);
+
+            let output = job
+                .planned_output_path()
+                .with_context(|| format!("Failed to plan {}", path.display()))?;
+
+            Ok(PlannedFile {
+                size,
+                format: format.map(|f| f.name()),
+                rule: job.rule.clone(),
+                sheet: match format {
+                    Some(FileFormat::Xls | FileFormat::Xlsx | FileFormat::Ods) => Some(
+                        job.options
+                            .read
+                            .sheet
+                            .clone()
+                            .unwrap_or_else(|| "(first sheet)".to_string()),
+                    ),
+                    _ => None,
+                },
+                output,
+                partition_by: job.options.partition_by.clone(),
+                post_action: job.post_action,
+                move_to: job.move_to.clone(),
+                input: path,
+            })
+        })
+        .collect()
+}
+
+/// Prints the plan and, with `report`, also writes it there as JSON.
+pub fn print_plan(plan: &[PlannedFile], report: Option<&Path>, format: OutputFormat) -> Result<()> {
+    let json = serde_json::to_string_pretty(&Plan {
+        dry_run: true,
+        files: plan,
+    })
+    .context("Failed to serialize plan")?;
+
+    if let Some(path) = report {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).context("Failed to create report directory")?;
+        }
+        fs::write(path, &json)
+            .with_context(|| format!("Failed to write report: {}", path.display()))?;
+    }
+
+    if format == OutputFormat::Json {
+        println!("{}", json);
+        return Ok(());
+    }
+
+    for file in plan {
+        println!("{}", file.input.display());
+        println!(
+            "  format: {}  rule: {}  sheet: {}",
+            file.format.unwrap_or("-"),
+            file.rule.as_deref().unwrap_or("-"),
+            file.sheet.as_deref().unwrap_or("-")
+        );
+        if file.partition_by.is_empty() {
+            println!("  output: {}", file.output.display());
+        } else {
+            println!(
+                "  output: {} (partitioned by {})",
+                file.output.with_extension("").display(),
+                file.partition_by.join(", ")
+            );
+        }
+        match (file.post_action, &file.move_to) {
+            (PostAction::Move, Some(target)) => println!("  then: move to {}", target.display()),
+            (PostAction::Delete, _) => println!("  then: delete source"),
+            _ => println!("  then: keep source"),
+        }
+    }
+
+    println!("---");
+    println!(
+        "Dry run: {} file(s) would be converted, nothing was converted",
+        plan.len()
+    );
+    if let Some(path) = report {
+        println!("Plan written to {}", path.display());
+    }
+
+    Ok(())
+}
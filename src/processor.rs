@@ -1,74 +1,500 @@
 use anyhow::Result;
+use chrono::{DateTime, Local};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 use walkdir::WalkDir;
 
-use crate::config::Config;
-use crate::converter::convert;
+use crate::config::{Config, LocaleConfig, PostAction, QualityConfig, RuleConfig, StorageConfig};
+use crate::converter::{convert, ConversionResult, ConvertOptions};
+use crate::filter::FileFilter;
 use crate::format::FileFormat;
+use crate::metrics;
+use crate::progress::BatchProgress;
+use crate::report::{FileReport, FileStatus};
+use crate::reader::{self, ReadOptions};
+use crate::rules::RuleSet;
+use crate::shutdown;
+use crate::storage::{self, StagedFile};
+use crate::template::{self, TemplateContext};
 
+#[derive(Default)]
 pub struct ProcessStats {
     pub processed: usize,
     pub errors: usize,
+    /// Inputs left untried because the run stopped early
+    pub not_attempted: usize,
+    pub rows: u64,
+    pub rejected: u64,
+    pub skipped_lines: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub elapsed: Duration,
+    pub files: Vec<FileReport>,
 }
 
-pub fn process_batch(config: &Config) -> Result<ProcessStats> {
+impl ProcessStats {
+    pub fn record(&mut self, file: FileReport) {
+        match file.status {
+            FileStatus::Ok => {
+                self.processed += 1;
+                self.rows += file.rows.unwrap_or(0) as u64;
+                self.rejected += file.rejected.unwrap_or(0) as u64;
+                self.skipped_lines += file.skipped_lines.unwrap_or(0) as u64;
+                self.bytes_in += file.input_bytes.unwrap_or(0);
+                self.bytes_out += file.output_bytes.unwrap_or(0);
+            }
+            FileStatus::Error => self.errors += 1,
+        }
+        self.files.push(file);
+    }
+
+    /// Source bytes per Parquet byte, e.g. 4.0 when the output is a quarter of the input.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.bytes_out > 0).then(|| self.bytes_in as f64 / self.bytes_out as f64)
+    }
+}
+
+/// Everything needed to convert one input, resolved from the config and matching rule.
+pub struct FileJob {
+    pub input: PathBuf,
+    pub relative: PathBuf,
+    pub output_dir: PathBuf,
+    pub output_template: String,
+    pub dataset: String,
+    pub rule: Option<String>,
+    pub options: ConvertOptions,
+    pub post_action: PostAction,
+    pub move_to: Option<PathBuf>,
+}
+
+impl FileJob {
+    /// Resolves the output path from the template, using `source` (the staged input)
+    /// for `{mtime}`, `{hash}` and `{sheet}`.
+    pub fn output_path(&self, source: &Path) -> Result<PathBuf> {
+        let content_hash = if template::needs_hash(&self.output_template) {
+            Some(template::hash_file(source)?)
+        } else {
+            None
+        };
+        let sheet = if template::needs_sheet(&self.output_template) {
+            reader::resolve_sheet(source, &self.options.read)?
+        } else {
+            None
+        };
+
+        self.render_output(modified_time(source), content_hash, sheet.as_deref())
+    }
+
+    /// The output path without reading the input. `{hash}` is left as a placeholder,
+    /// as are `{mtime}` for remote inputs and `{sheet}` when no sheet is configured.
+    pub fn planned_output_path(&self) -> Result<PathBuf> {
+        let modified = if storage::is_remote(&self.input) {
+            None
+        } else {
+            modified_time(&self.input)
+        };
+
+        self.render_output(modified, None, self.options.read.sheet.as_deref())
+    }
+
+    fn render_output(
+        &self,
+        modified: Option<DateTime<Local>>,
+        content_hash: Option<String>,
+        sheet: Option<&str>,
+    ) -> Result<PathBuf> {
+        let ctx = TemplateContext {
+            relative: &self.relative,
+            format: FileFormat::from_path(&self.relative),
+            sheet,
+            dataset: &self.dataset,
+            modified,
+            now: Local::now(),
+            content_hash,
+        };
+
+        Ok(self
+            .output_dir
+            .join(template::render(&self.output_template, &ctx)?))
+    }
+}
+
+/// Converts every input file. With `fail_fast`, stops after the first failure.
+pub fn process_batch(config: &Config, fail_fast: bool) -> Result<ProcessStats> {
     print_header(config);
 
-    let mut stats = ProcessStats { processed: 0, errors: 0 };
+    let start = Instant::now();
+    let planner = Planner::new(config)?;
+    let inputs = collect_inputs(config)?;
+
+    let mut stats = ProcessStats::default();
+
+    let total_bytes = inputs.iter().map(|(_, size)| size).sum();
+    let mut progress = BatchProgress::new(&config.logging, inputs.len(), total_bytes);
 
-    for entry in create_walker(config).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
+    let total = inputs.len();
+    for (index, (path, size)) in inputs.into_iter().enumerate() {
+        metrics::set_queue_depth(total - index);
+
+        if shutdown::requested() {
+            info!(signal = shutdown::signal_name(), "Shutdown requested, stopping batch");
+            stats.not_attempted = total - index;
+            break;
+        }
 
-        if path.is_file() && FileFormat::is_supported(path) {
-            let output = get_output_path(path, &config.general.input_dir, &config.general.output_dir);
+        progress.start_file(&path);
+        let file_start = Instant::now();
+        let job = planner.plan(&path, &config.general.input_dir, config);
 
-            match process_file(path, &output, config) {
-                Ok(_) => stats.processed += 1,
-                Err(e) => {
-                    eprintln!("[ERROR] {}: {}", path.display(), e);
-                    stats.errors += 1;
+        match run_job(&job, config) {
+            Ok(result) => {
+                progress.finish_file(size, result.rows);
+                stats.record(FileReport::converted(&path, &result, file_start.elapsed()));
+            }
+            Err(e) => {
+                error!(input = %path.display(), error = %format!("{:#}", e), "Conversion failed");
+                progress.finish_file(size, 0);
+                stats.record(FileReport::failed(&path, &e, file_start.elapsed()));
+
+                if fail_fast {
+                    stats.not_attempted = total - index - 1;
+                    warn!(
+                        not_attempted = stats.not_attempted,
+                        "Stopping at the first failure (--fail-fast)"
+                    );
+                    break;
                 }
             }
         }
     }
 
-    println!("---");
-    println!("Processed: {} | Errors: {}", stats.processed, stats.errors);
+    metrics::set_queue_depth(0);
+    progress.finish();
+    stats.elapsed = start.elapsed();
+
+    info!(
+        processed = stats.processed,
+        errors = stats.errors,
+        not_attempted = stats.not_attempted,
+        rows = stats.rows,
+        rejected = stats.rejected,
+        skipped_lines = stats.skipped_lines,
+        bytes_in = stats.bytes_in,
+        bytes_out = stats.bytes_out,
+        compression_ratio = stats.compression_ratio().map(|r| (r * 100.0).round() / 100.0),
+        elapsed_ms = stats.elapsed.as_millis() as u64,
+        "Batch finished"
+    );
 
     Ok(stats)
 }
 
-pub fn process_file(input: &Path, output: &Path, config: &Config) -> Result<()> {
-    println!("[CONVERTING] {}", input.display());
+/// The parts of a config that jobs are planned from, compiled once per batch or
+/// reload rather than per file.
+pub struct Planner {
+    rules: RuleSet,
+    config_hash: String,
+}
+
+impl Planner {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            rules: RuleSet::new(&config.rules)?,
+            config_hash: config.fingerprint(),
+        })
+    }
+
+    /// Converts a single file found below `input_dir`, applying the matching rule.
+    pub fn process_file(&self, input: &Path, input_dir: &Path, config: &Config) -> Result<()> {
+        let job = self.plan(input, input_dir, config);
+        run_job(&job, config)?;
+        Ok(())
+    }
+
+    /// `config` must be the one the planner was built from.
+    pub fn plan(&self, input: &Path, input_dir: &Path, config: &Config) -> FileJob {
+        plan_file(input, input_dir, config, &self.rules, &self.config_hash)
+    }
+}
+
+fn plan_file(
+    input: &Path,
+    input_dir: &Path,
+    config: &Config,
+    rules: &RuleSet,
+    config_hash: &str,
+) -> FileJob {
+    let relative = relative_path(input, input_dir);
+    let rule = rules.find(&relative);
+
+    let output_dir = rule
+        .and_then(|r| r.output_dir.as_ref())
+        .unwrap_or(&config.general.output_dir);
+
+    let default_action = if config.general.delete_source {
+        PostAction::Delete
+    } else {
+        PostAction::Keep
+    };
+
+    let output_template = rule
+        .and_then(|r| r.output_template.as_deref())
+        .or(config.general.output_template.as_deref())
+        .unwrap_or(template::DEFAULT_TEMPLATE);
+
+    FileJob {
+        input: input.to_path_buf(),
+        output_dir: output_dir.clone(),
+        output_template: output_template.to_string(),
+        dataset: dataset_name(&relative, rule),
+        rule: rule.and_then(|r| {
+            r.name
+                .clone()
+                .or_else(|| r.glob.clone())
+                .or_else(|| r.regex.clone())
+        }),
+        options: ConvertOptions {
+            read: read_options(config, rule),
+            schema_file: rule.and_then(|r| r.schema_file.clone()),
+            partition_by: rule.map(|r| r.partition_by.clone()).unwrap_or_default(),
+            verify: config.processing.verify,
+            config_hash: Some(config_hash.to_string()),
+            lineage: config.lineage.enabled.then(|| config.lineage.clone()),
+            quality: quality_config(config, rule),
+            locale: locale_config(config, rule),
+        },
+        post_action: rule.and_then(|r| r.post_action).unwrap_or(default_action),
+        move_to: rule
+            .and_then(|r| r.move_to.as_ref())
+            .map(|dir| dir.join(&relative)),
+        relative,
+    }
+}
+
+/// `path` relative to `dir`, also when one is given as `./in` and the other as `in` or
+/// an absolute path. Paths outside `dir` are returned as they are.
+pub fn relative_path(path: &Path, dir: &Path) -> PathBuf {
+    if let Ok(relative) = path.strip_prefix(dir) {
+        return relative.to_path_buf();
+    }
+
+    if !storage::is_remote(path) {
+        if let (Ok(path), Ok(dir)) = (path.canonicalize(), dir.canonicalize()) {
+            if let Ok(relative) = path.strip_prefix(dir) {
+                return relative.to_path_buf();
+            }
+        }
+    }
+
+    path.to_path_buf()
+}
+
+fn modified_time(path: &Path) -> Option<DateTime<Local>> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(DateTime::<Local>::from)
+}
+
+/// The rule name, else the first directory below the input directory, else the file stem.
+fn dataset_name(relative: &Path, rule: Option<&RuleConfig>) -> String {
+    if let Some(name) = rule.and_then(|r| r.name.as_ref()) {
+        return name.clone();
+    }
 
-    let result = convert(input, output, config.general.default_sheet.as_deref())?;
+    let parent = relative.parent().unwrap_or(Path::new(""));
+    match parent.components().find(|c| matches!(c, Component::Normal(_))) {
+        Some(dir) => dir.as_os_str().to_string_lossy().into_owned(),
+        None => relative
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    }
+}
 
-    println!(
-        "  Format: {:?} | Rows: {} | Columns: {}",
-        result.format, result.rows, result.columns
+pub fn run_job(job: &FileJob, config: &Config) -> Result<ConversionResult> {
+    let start = Instant::now();
+
+    if config.logging.show_progress {
+        info!(input = %job.input.display(), rule = job.rule.as_deref(), "Converting");
+    }
+
+    let result = match convert_job(job, config) {
+        Ok(result) => result,
+        Err(e) => {
+            metrics::record_failure(FileFormat::from_path(&job.relative), start.elapsed());
+            return Err(e);
+        }
+    };
+    metrics::record_success(result.format, result.rows, result.input_bytes, start.elapsed());
+
+    info!(
+        input = %job.input.display(),
+        output = %display_paths(&result.outputs),
+        rule = job.rule.as_deref(),
+        format = ?result.format,
+        rows = result.rows,
+        rejected = result.rejected,
+        skipped_lines = result.skipped_lines,
+        columns = result.columns,
+        bytes_in = result.input_bytes,
+        bytes_out = result.output_bytes,
+        duration_ms = start.elapsed().as_millis() as u64,
+        "Converted"
     );
-    println!("[OK] {}", output.display());
 
-    if config.general.delete_source {
-        delete_source_file(input);
+    apply_post_action(job, &config.storage);
+
+    Ok(result)
+}
+
+fn convert_job(job: &FileJob, config: &Config) -> Result<ConversionResult> {
+    // The template may use the source's timestamp or content, so resolve it after staging
+    let staged_input = storage::stage_input(&job.input, &config.storage)?;
+    let output = job.output_path(staged_input.path())?;
+
+    convert_staged(&staged_input, &output, &job.options, &config.storage)
+}
+
+/// The rule's quality checks, else the top-level ones; `None` when there are none.
+pub fn quality_config(config: &Config, rule: Option<&RuleConfig>) -> Option<QualityConfig> {
+    let quality = rule
+        .and_then(|r| r.quality.as_ref())
+        .unwrap_or(&config.quality);
+
+    (!quality.columns.is_empty()).then(|| quality.clone())
+}
+
+/// Locale settings for a file: the rule's `[rules.locale]`, else `[locale]`.
+pub fn locale_config(config: &Config, rule: Option<&RuleConfig>) -> LocaleConfig {
+    rule.and_then(|r| r.locale.clone())
+        .unwrap_or_else(|| config.locale.clone())
+}
+
+/// Reader settings for a file: rule overrides first, then `[processing]` and `[general]`.
+pub fn read_options(config: &Config, rule: Option<&RuleConfig>) -> ReadOptions {
+    let overrides = rule.map(|r| r.reader.clone()).unwrap_or_default();
+
+    ReadOptions {
+        sheet: overrides.sheet.or_else(|| config.general.default_sheet.clone()),
+        csv_delimiter: overrides
+            .csv_delimiter
+            .or(config.processing.csv_delimiter)
+            .map(|c| c as u8),
+        csv_has_header: overrides
+            .csv_has_header
+            .or(config.processing.csv_has_header),
+        csv_quote_char: overrides
+            .csv_quote_char
+            .or(config.processing.csv_quote_char)
+            .map(|c| c as u8),
+        csv_decimal_comma: overrides
+            .csv_decimal_comma
+            .or(config.processing.csv_decimal_comma),
+        csv_bad_lines: overrides
+            .csv_bad_lines
+            .unwrap_or(config.processing.csv_bad_lines),
+        csv_truncate_ragged_lines: overrides
+            .csv_truncate_ragged_lines
+            .unwrap_or(config.processing.csv_truncate_ragged_lines),
+        csv_missing_is_null: overrides
+            .csv_missing_is_null
+            .unwrap_or(config.processing.csv_missing_is_null),
+        encoding: overrides
+            .encoding
+            .or_else(|| config.processing.encoding.clone()),
+        infer_schema_rows: overrides
+            .infer_schema_rows
+            .unwrap_or(config.processing.infer_schema_rows),
+        xml_record_path: overrides.xml_record_path,
+        json_pointer: overrides.json_pointer,
+        text_columns: Vec::new(),
+        max_rows: None,
     }
+}
 
-    Ok(())
+/// Converts a file where either side may be an `s3://` URI.
+pub fn convert_file(
+    input: &Path,
+    output: &Path,
+    options: &ConvertOptions,
+    storage_config: &StorageConfig,
+) -> Result<ConversionResult> {
+    let staged_input = storage::stage_input(input, storage_config)?;
+    convert_staged(&staged_input, output, options, storage_config)
 }
 
-pub fn get_output_path(input: &Path, input_dir: &Path, output_dir: &Path) -> PathBuf {
-    let relative = input.strip_prefix(input_dir).unwrap_or(input);
-    let mut output = output_dir.join(relative);
-    output.set_extension("parquet");
-    output
+fn convert_staged(
+    staged_input: &StagedFile,
+    output: &Path,
+    options: &ConvertOptions,
+    storage_config: &StorageConfig,
+) -> Result<ConversionResult> {
+    let staged_output = storage::stage_output(output)?;
+
+    let mut result = convert(
+        staged_input.path(),
+        &staged_input.origin(),
+        staged_output.path(),
+        options,
+    )?;
+
+    result.outputs = storage::publish(&staged_output, &result.outputs, storage_config)?;
+    if let Some(rejects) = result.rejects.take() {
+        result.rejects = storage::publish(&staged_output, &[rejects], storage_config)?.pop();
+    }
+    if let Some(bad_lines) = result.bad_lines.take() {
+        result.bad_lines = storage::publish(&staged_output, &[bad_lines], storage_config)?.pop();
+    }
+
+    Ok(result)
 }
 
 fn print_header(config: &Config) {
-    println!("Processing directory: {}", config.general.input_dir.display());
-    println!("Output: {}", config.general.output_dir.display());
-    println!("Supported extensions: {:?}", FileFormat::supported_extensions());
-    println!("---");
+    info!(
+        input_dir = %config.general.input_dir.display(),
+        output_dir = %config.general.output_dir.display(),
+        extensions = ?config.processing.extensions,
+        "Processing directory"
+    );
+}
+
+pub fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Input files with their sizes, after the `[processing]` filters.
+pub fn collect_inputs(config: &Config) -> Result<Vec<(PathBuf, u64)>> {
+    let filter = FileFilter::new(&config.processing)?;
+    let input_dir = &config.general.input_dir;
+
+    let candidates: Vec<(PathBuf, u64)> = if storage::is_remote(input_dir) {
+        storage::list_dir(input_dir, config.general.recursive, &config.storage)?
+    } else {
+        create_walker(config)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| {
+                let size = e.metadata().ok()?.len();
+                Some((e.into_path(), size))
+            })
+            .collect()
+    };
+
+    Ok(candidates
+        .into_iter()
+        .filter(|(path, size)| {
+            let relative = path.strip_prefix(input_dir).unwrap_or(path);
+            filter.matches(relative, Some(*size))
+        })
+        .collect())
 }
 
 fn create_walker(config: &Config) -> WalkDir {
@@ -80,9 +506,34 @@ fn create_walker(config: &Config) -> WalkDir {
     }
 }
 
-fn delete_source_file(path: &Path) {
-    match fs::remove_file(path) {
-        Ok(_) => println!("[DELETED] {}", path.display()),
-        Err(e) => eprintln!("[WARN] Could not delete {}: {}", path.display(), e),
+fn apply_post_action(job: &FileJob, storage_config: &StorageConfig) {
+    match job.post_action {
+        PostAction::Keep => {}
+        PostAction::Delete => match storage::remove(&job.input, storage_config) {
+            Ok(_) => info!(input = %job.input.display(), "Deleted source"),
+            Err(e) => warn!(
+                input = %job.input.display(),
+                error = %format!("{:#}", e),
+                "Could not delete source"
+            ),
+        },
+        PostAction::Move => {
+            let Some(ref target) = job.move_to else {
+                return;
+            };
+            match storage::move_file(&job.input, target, storage_config) {
+                Ok(_) => info!(
+                    input = %job.input.display(),
+                    target = %target.display(),
+                    "Moved source"
+                ),
+                Err(e) => warn!(
+                    input = %job.input.display(),
+                    target = %target.display(),
+                    error = %format!("{:#}", e),
+                    "Could not move source"
+                ),
+            }
+        }
     }
 }
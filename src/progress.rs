@@ -0,0 +1,115 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::LoggingConfig;
+
+const BAR_TEMPLATE: &str =
+    "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ETA {eta} | {msg}";
+
+/// Bytes only advance when a file finishes, so a lone file gets a spinner
+/// instead of a bar that sits at zero with no ETA.
+const SPINNER_TEMPLATE: &str = "{spinner:.green} [{elapsed_precise}] {total_bytes} | {msg}";
+
+/// The bar currently on screen, so log lines can be printed above it.
+static ACTIVE: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Runs `f` with the progress bar hidden, if one is shown.
+pub fn suspend<T>(f: impl FnOnce() -> T) -> T {
+    let active = ACTIVE.lock().ok().and_then(|bar| bar.clone());
+
+    match active {
+        Some(bar) => bar.suspend(f),
+        None => f(),
+    }
+}
+
+/// Files done/total, bytes read, rows per second and ETA for a batch run, or a
+/// spinner when the batch has a single file.
+/// Hidden unless `progress_bar` is set and the output is a terminal.
+pub struct BatchProgress {
+    bar: Option<ProgressBar>,
+    files_total: usize,
+    files_done: usize,
+    rows: u64,
+    start: Instant,
+}
+
+impl BatchProgress {
+    pub fn new(config: &LoggingConfig, files_total: usize, bytes_total: u64) -> Self {
+        let enabled =
+            config.progress_bar && io::stdout().is_terminal() && io::stderr().is_terminal();
+
+        let bar = enabled.then(|| {
+            let bar =
+                ProgressBar::with_draw_target(Some(bytes_total), ProgressDrawTarget::stderr());
+            let template = if files_total == 1 {
+                SPINNER_TEMPLATE
+            } else {
+                BAR_TEMPLATE
+            };
+            if let Ok(style) = ProgressStyle::with_template(template) {
+                bar.set_style(style.progress_chars("=> "));
+            }
+            bar.enable_steady_tick(Duration::from_millis(120));
+
+            if let Ok(mut active) = ACTIVE.lock() {
+                *active = Some(bar.clone());
+            }
+            bar
+        });
+
+        Self {
+            bar,
+            files_total,
+            files_done: 0,
+            rows: 0,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn start_file(&self, path: &Path) {
+        if let Some(ref bar) = self.bar {
+            let name = path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy();
+            bar.set_message(format!("{} | {}", self.counts(), name));
+        }
+    }
+
+    pub fn finish_file(&mut self, bytes: u64, rows: usize) {
+        self.files_done += 1;
+        self.rows += rows as u64;
+
+        if let Some(ref bar) = self.bar {
+            bar.inc(bytes);
+            bar.set_message(self.counts());
+        }
+    }
+
+    pub fn finish(self) {
+        if let Some(bar) = self.bar {
+            bar.finish_and_clear();
+            if let Ok(mut active) = ACTIVE.lock() {
+                *active = None;
+            }
+        }
+    }
+
+    fn counts(&self) -> String {
+        let secs = self.start.elapsed().as_secs_f64();
+        let rate = if secs > 0.0 {
+            self.rows as f64 / secs
+        } else {
+            0.0
+        };
+
+        format!(
+            "{}/{} files | {:.0} rows/s",
+            self.files_done, self.files_total, rate
+        )
+    }
+}
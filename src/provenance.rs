@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use polars::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::LineageConfig;
+use crate::converter::ConvertOptions;
+use crate::format::FileFormat;
+use crate::reader;
+use crate::storage;
+use crate::template;
+
+/// Prefix of the key-value metadata keys written to every Parquet file.
+pub const KEY_PREFIX: &str = "parse_to_parquet.";
+
+/// Key-value metadata describing where a Parquet file came from.
+///
+/// `input` is the local file that is read, `origin` where it was staged from,
+/// which differs for files downloaded from S3.
+pub fn metadata(
+    input: &Path,
+    origin: &Path,
+    format: FileFormat,
+    options: &ConvertOptions,
+    converted_at: DateTime<Utc>,
+) -> Result<Vec<(String, String)>> {
+    let source = fs::metadata(input).context("Failed to read source file metadata")?;
+
+    let mut entries = vec![
+        ("source_path", source_path(origin).display().to_string()),
+        ("source_size", source.len().to_string()),
+    ];
+    if let Ok(modified) = source.modified() {
+        entries.push(("source_mtime", timestamp(modified.into())));
+    }
+    entries.push(("source_sha256", template::hash_file(input)?));
+    entries.push(("format", format.name().to_string()));
+
+    match format {
+        FileFormat::Xls | FileFormat::Xlsx | FileFormat::Ods => {
+            if let Some(sheet) = sheet(input, format, options)? {
+                entries.push(("sheet", sheet));
+            }
+        }
+        FileFormat::Xml => {
+            let tag = reader::xml_record_tag(input, &options.read)?;
+            if let Some(tag) = tag {
+                entries.push(("xml_record_tag", tag));
+            }
+        }
+        _ => {}
+    }
+
+    entries.push(("tool_version", env!("CARGO_PKG_VERSION").to_string()));
+    if let Some(ref hash) = options.config_hash {
+        entries.push(("config_sha256", hash.clone()));
+    }
+    entries.push(("converted_at", timestamp(converted_at)));
+
+    Ok(entries
+        .into_iter()
+        .map(|(key, value)| (format!("{}{}", KEY_PREFIX, key), value))
+        .collect())
+}
+
+/// Appends the `[lineage]` columns to `df`. The row number is each row's 1-based
+/// position in `df`, so it has to be added before any rows are dropped.
+pub fn append_lineage(
+    df: &mut DataFrame,
+    lineage: &LineageConfig,
+    input: &Path,
+    origin: &Path,
+    format: FileFormat,
+    options: &ConvertOptions,
+    ingested_at: DateTime<Utc>,
+) -> Result<()> {
+    for name in lineage.columns() {
+        if df.get_column_index(name).is_some() {
+            return Err(anyhow!(
+                "Lineage column '{}' already exists in the source",
+                name
+            ));
+        }
+    }
+
+    let height = df.height();
+    let source_file = source_path(origin).display().to_string();
+    let sheet = sheet(input, format, options)?;
+
+    let ingested_at = Column::new(
+        lineage.ingested_at.as_str().into(),
+        vec![ingested_at.timestamp_millis(); height],
+    )
+    .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))
+    .context("Failed to build lineage column")?;
+
+    let columns = [
+        Column::new(
+            lineage.source_file.as_str().into(),
+            vec![source_file; height],
+        ),
+        Column::new(lineage.source_sheet.as_str().into(), vec![sheet; height]),
+        Column::new(
+            lineage.row_number.as_str().into(),
+            (1..=height as u64).collect::<Vec<_>>(),
+        ),
+        ingested_at,
+    ];
+
+    df.hstack_mut(&columns)
+        .context("Failed to add lineage columns")?;
+
+    Ok(())
+}
+
+/// The absolute source path, or the `s3://` URI.
+fn source_path(origin: &Path) -> PathBuf {
+    if storage::is_remote(origin) {
+        origin.to_path_buf()
+    } else {
+        origin
+            .canonicalize()
+            .unwrap_or_else(|_| origin.to_path_buf())
+    }
+}
+
+/// The sheet that is read from a workbook: the configured one, else the first.
+fn sheet(input: &Path, format: FileFormat, options: &ConvertOptions) -> Result<Option<String>> {
+    match format {
+        FileFormat::Xls | FileFormat::Xlsx | FileFormat::Ods => match options.read.sheet {
+            Some(ref sheet) => Ok(Some(sheet.clone())),
+            None => Ok(reader::sheet_names(input)?.into_iter().next()),
+        },
+        _ => Ok(None),
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
@@ -0,0 +1,364 @@
+use anyhow::{anyhow, Context, Result};
+use polars::prelude::*;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::{ColumnChecks, QualityConfig};
+
+/// Suffix of the file rejected rows are written to, next to the output.
+const REJECTS_SUFFIX: &str = "_rejects.parquet";
+
+/// The compiled `[quality]` checks.
+pub struct QualityChecks {
+    columns: Vec<ColumnRule>,
+    max_reject_percent: Option<f64>,
+    reason_column: String,
+}
+
+struct ColumnRule {
+    name: String,
+    not_null: bool,
+    regex: Option<Regex>,
+    min: Option<f64>,
+    max: Option<f64>,
+    allowed: Vec<String>,
+    unique: bool,
+}
+
+/// Rows of one file, split by the quality checks.
+pub struct Checked {
+    pub valid: DataFrame,
+    /// Rejected rows with the reason column appended; empty when every row passed
+    pub rejects: DataFrame,
+}
+
+impl QualityChecks {
+    pub fn new(config: &QualityConfig) -> Result<Self> {
+        if let Some(percent) = config.max_reject_percent {
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(anyhow!("max_reject_percent must be between 0 and 100"));
+            }
+        }
+
+        if config.reason_column.is_empty() {
+            return Err(anyhow!("reason_column must not be empty"));
+        }
+
+        let columns = config
+            .columns
+            .iter()
+            .map(|(name, checks)| {
+                ColumnRule::new(name, checks)
+                    .with_context(|| format!("Invalid checks for '{}'", name))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            columns,
+            max_reject_percent: config.max_reject_percent,
+            reason_column: config.reason_column.clone(),
+        })
+    }
+
+    /// Splits `df` into passing and rejected rows. Fails when a checked column is
+    /// missing or the reject percentage is above the threshold.
+    pub fn apply(&self, df: &DataFrame) -> Result<Checked> {
+        let mut reasons: Vec<Vec<String>> = vec![Vec::new(); df.height()];
+
+        for rule in &self.columns {
+            let column = df
+                .column(&rule.name)
+                .with_context(|| format!("Quality check column '{}' not found", rule.name))?;
+            rule.check(column, &mut reasons)?;
+        }
+
+        let rejected: Vec<bool> = reasons.iter().map(|r| !r.is_empty()).collect();
+        let count = rejected.iter().filter(|r| **r).count();
+
+        if let Some(limit) = self.max_reject_percent {
+            let percent = if df.height() == 0 {
+                0.0
+            } else {
+                count as f64 * 100.0 / df.height() as f64
+            };
+            if percent > limit {
+                return Err(anyhow!(
+                    "{} of {} rows rejected ({:.1}%), above max_reject_percent = {}",
+                    count,
+                    df.height(),
+                    percent,
+                    limit
+                ));
+            }
+        }
+
+        if df.get_column_index(&self.reason_column).is_some() {
+            return Err(anyhow!(
+                "Reason column '{}' already exists in the source",
+                self.reason_column
+            ));
+        }
+
+        let mask = BooleanChunked::from_slice("rejected".into(), &rejected);
+        let valid = df.filter(&!&mask).context("Failed to filter valid rows")?;
+        let mut rejects = df.filter(&mask).context("Failed to filter rejected rows")?;
+
+        let reason = Column::new(
+            self.reason_column.as_str().into(),
+            reasons
+                .into_iter()
+                .filter(|r| !r.is_empty())
+                .map(|r| r.join("; "))
+                .collect::<Vec<_>>(),
+        );
+        rejects
+            .hstack_mut(&[reason])
+            .context("Failed to add reason column")?;
+
+        Ok(Checked { valid, rejects })
+    }
+}
+
+impl ColumnRule {
+    fn new(name: &str, checks: &ColumnChecks) -> Result<Self> {
+        if let (Some(min), Some(max)) = (checks.min, checks.max) {
+            if min > max {
+                return Err(anyhow!("min is greater than max"));
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            not_null: checks.not_null,
+            regex: checks
+                .regex
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .context("Invalid regex")?,
+            min: checks.min,
+            max: checks.max,
+            allowed: checks.allowed.clone(),
+            unique: checks.unique,
+        })
+    }
+
+    /// Adds a reason to every row of `column` that fails a check. Values are compared
+    /// as text, except for `min` and `max`.
+    fn check(&self, column: &Column, reasons: &mut [Vec<String>]) -> Result<()> {
+        let text = column
+            .cast(&DataType::String)
+            .with_context(|| format!("Failed to read column '{}' as text", self.name))?;
+        let text = text.str()?;
+
+        let numbers = if self.min.is_some() || self.max.is_some() {
+            let numbers = column
+                .cast(&DataType::Float64)
+                .with_context(|| format!("Failed to read column '{}' as numbers", self.name))?;
+            Some(numbers.f64()?.clone())
+        } else {
+            None
+        };
+
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+
+        for (index, value) in text.iter().enumerate() {
+            let Some(value) = value else {
+                if self.not_null {
+                    reasons[index].push(format!("{}: null", self.name));
+                }
+                continue;
+            };
+
+            if let Some(ref regex) = self.regex {
+                if !regex.is_match(value) {
+                    reasons[index].push(format!("{}: does not match {}", self.name, regex));
+                }
+            }
+
+            if !self.allowed.is_empty() && !self.allowed.iter().any(|a| a == value) {
+                reasons[index].push(format!(
+                    "{}: '{}' is not an allowed value",
+                    self.name, value
+                ));
+            }
+
+            if let Some(ref numbers) = numbers {
+                match numbers.get(index) {
+                    None => reasons[index].push(format!("{}: not a number", self.name)),
+                    Some(n) => {
+                        if let Some(min) = self.min.filter(|min| n < *min) {
+                            reasons[index]
+                                .push(format!("{}: {} is below {}", self.name, value, min));
+                        }
+                        if let Some(max) = self.max.filter(|max| n > *max) {
+                            reasons[index]
+                                .push(format!("{}: {} is above {}", self.name, value, max));
+                        }
+                    }
+                }
+            }
+
+            if self.unique {
+                *seen.entry(value).or_default() += 1;
+            }
+        }
+
+        if self.unique {
+            for (index, value) in text.iter().enumerate() {
+                if value.is_some_and(|v| seen.get(v).is_some_and(|count| *count > 1)) {
+                    reasons[index].push(format!("{}: duplicate value", self.name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `sales.parquet` gives `sales_rejects.parquet`, also for a partitioned `sales/` dataset.
+pub fn rejects_path(output: &Path) -> PathBuf {
+    let mut name = output.file_stem().unwrap_or_default().to_os_string();
+    name.push(REJECTS_SUFFIX);
+    output.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checks(toml: &str) -> QualityChecks {
+        let config: QualityConfig = toml::from_str(toml).unwrap();
+        QualityChecks::new(&config).unwrap()
+    }
+
+    fn orders() -> DataFrame {
+        df!(
+            "id" => ["A1", "A2", "A2", "b4", "A5"],
+            "amount" => [Some("10"), Some("250"), None, Some("x"), Some("-1")],
+            "status" => ["open", "closed", "open", "lost", "open"],
+        )
+        .unwrap()
+    }
+
+    fn reasons(checked: &Checked) -> Vec<String> {
+        checked
+            .rejects
+            .column("_reject_reason")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn rows_passing_every_check_are_kept() {
+        let checked = checks(
+            r#"
+            [columns.amount]
+            not_null = true
+            min = 0
+            max = 100
+            "#,
+        )
+        .apply(&orders())
+        .unwrap();
+
+        assert_eq!(checked.valid.height(), 1);
+        assert_eq!(checked.rejects.height(), 4);
+        assert_eq!(
+            reasons(&checked),
+            [
+                "amount: 250 is above 100",
+                "amount: null",
+                "amount: not a number",
+                "amount: -1 is below 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn regex_allowed_and_unique_add_one_reason_each() {
+        let checked = checks(
+            r#"
+            [columns.id]
+            regex = "^A[0-9]+$"
+            unique = true
+
+            [columns.status]
+            allowed = ["open", "closed"]
+            "#,
+        )
+        .apply(&orders())
+        .unwrap();
+
+        assert_eq!(checked.valid.height(), 2);
+        assert_eq!(
+            reasons(&checked),
+            [
+                "id: duplicate value",
+                "id: duplicate value",
+                "id: does not match ^A[0-9]+$; status: 'lost' is not an allowed value",
+            ]
+        );
+    }
+
+    #[test]
+    fn too_many_rejects_fail_the_file() {
+        let quality = checks(
+            r#"
+            max_reject_percent = 50
+            [columns.status]
+            allowed = ["open"]
+            "#,
+        );
+        assert!(quality.apply(&orders()).is_ok());
+
+        let quality = checks(
+            r#"
+            max_reject_percent = 30
+            [columns.status]
+            allowed = ["open"]
+            "#,
+        );
+        let err = quality.apply(&orders()).err().unwrap();
+        assert!(err.to_string().contains("2 of 5 rows rejected (40.0%)"));
+    }
+
+    #[test]
+    fn invalid_checks_are_rejected() {
+        for toml in [
+            "max_reject_percent = 120",
+            "reason_column = \"\"",
+            "[columns.amount]\nmin = 10\nmax = 1",
+            "[columns.id]\nregex = \"(\"",
+        ] {
+            let config: QualityConfig = toml::from_str(toml).unwrap();
+            assert!(QualityChecks::new(&config).is_err(), "{}", toml);
+        }
+    }
+
+    #[test]
+    fn missing_and_clashing_columns_fail() {
+        let missing = checks("[columns.price]\nnot_null = true");
+        assert!(missing.apply(&orders()).is_err());
+
+        let clash = checks("reason_column = \"status\"\n[columns.id]\nunique = true");
+        assert!(clash.apply(&orders()).is_err());
+    }
+
+    #[test]
+    fn rejects_file_sits_next_to_the_output() {
+        assert_eq!(
+            rejects_path(Path::new("out/sales.parquet")),
+            Path::new("out/sales_rejects.parquet")
+        );
+        assert_eq!(
+            rejects_path(Path::new("out/sales")),
+            Path::new("out/sales_rejects.parquet")
+        );
+    }
+}
@@ -1,22 +1,155 @@
 use anyhow::{anyhow, Result};
 use polars::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 use crate::format::FileFormat;
 
+pub use dialect::Dialect;
+
 mod csv_reader;
+mod dialect;
+mod encoding;
 mod excel_reader;
 mod json_reader;
 mod xml_reader;
 
-pub fn read_file(path: &Path, sheet: Option<&str>) -> Result<DataFrame> {
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    pub sheet: Option<String>,
+    /// CSV dialect settings; the ones not set are sniffed, see [`Dialect`]
+    pub csv_delimiter: Option<u8>,
+    pub csv_has_header: Option<bool>,
+    pub csv_quote_char: Option<u8>,
+    pub csv_decimal_comma: Option<bool>,
+    pub csv_bad_lines: BadLinePolicy,
+    pub csv_truncate_ragged_lines: bool,
+    pub csv_missing_is_null: bool,
+    /// Encoding of CSV and XML files; detected when not set
+    pub encoding: Option<String>,
+    pub infer_schema_rows: usize,
+    pub xml_record_path: Option<String>,
+    pub json_pointer: Option<String>,
+    /// CSV columns kept as text rather than inferred, so a schema file can type them
+    /// with locale settings
+    pub text_columns: Vec<String>,
+    /// Stop after this many records, for previews
+    pub max_rows: Option<usize>,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            sheet: None,
+            csv_delimiter: None,
+            csv_has_header: None,
+            csv_quote_char: None,
+            csv_decimal_comma: None,
+            csv_bad_lines: BadLinePolicy::Error,
+            csv_truncate_ragged_lines: false,
+            csv_missing_is_null: true,
+            encoding: None,
+            infer_schema_rows: 1000,
+            xml_record_path: None,
+            json_pointer: None,
+            text_columns: Vec::new(),
+            max_rows: None,
+        }
+    }
+}
+
+/// What happens to a CSV record the parser cannot read, such as one with more fields
+/// than the header or a badly quoted field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BadLinePolicy {
+    /// Fail the whole file
+    #[default]
+    Error,
+    /// Drop the record and count it
+    Skip,
+    /// Drop the record and write it to a bad lines file next to the output
+    Capture,
+}
+
+/// A CSV record dropped under [`BadLinePolicy::Skip`] or [`BadLinePolicy::Capture`].
+#[derive(Debug, Clone)]
+pub struct BadLine {
+    /// 1-based line number where the record starts
+    pub line: usize,
+    pub content: String,
+    pub reason: String,
+}
+
+pub fn read_file(path: &Path, options: &ReadOptions) -> Result<DataFrame> {
+    read_file_checked(path, options).map(|(df, _)| df)
+}
+
+/// Like [`read_file`], also returning the CSV records dropped by the bad-line policy.
+pub fn read_file_checked(path: &Path, options: &ReadOptions) -> Result<(DataFrame, Vec<BadLine>)> {
     let format = FileFormat::from_path(path)
         .ok_or_else(|| anyhow!("Unsupported format: {:?}", path.extension()))?;
 
-    match format {
-        FileFormat::Csv => csv_reader::read(path),
-        FileFormat::Json => json_reader::read(path),
-        FileFormat::Xml => xml_reader::read(path),
-        FileFormat::Xls | FileFormat::Xlsx | FileFormat::Ods => excel_reader::read(path, sheet),
+    let df = match format {
+        FileFormat::Csv => return csv_reader::read(path, options),
+        // The whole document is parsed either way, so the limit applies afterwards
+        FileFormat::Json => json_reader::read(path, options.json_pointer.as_deref())?
+            .head(options.max_rows),
+        FileFormat::Xml => xml_reader::read(path, options)?,
+        FileFormat::Xls | FileFormat::Xlsx | FileFormat::Ods => {
+            excel_reader::read(path, options.sheet.as_deref(), options.max_rows)?
+        }
+    };
+
+    Ok((df, Vec::new()))
+}
+
+/// Sheet names of a workbook, in workbook order.
+pub fn sheet_names(path: &Path) -> Result<Vec<String>> {
+    excel_reader::sheet_names(path)
+}
+
+/// The sheet `read_file` reads from a workbook: the configured one, else the first.
+/// `None` for other formats.
+pub fn resolve_sheet(path: &Path, options: &ReadOptions) -> Result<Option<String>> {
+    match FileFormat::from_path(path) {
+        Some(FileFormat::Xls | FileFormat::Xlsx | FileFormat::Ods) => {
+            excel_reader::resolve_sheet(path, options.sheet.as_deref()).map(Some)
+        }
+        _ => Ok(None),
     }
 }
+
+/// The record element `read_file` uses for an XML file: `xml_record_path` when set,
+/// otherwise the first element below the root.
+pub fn xml_record_tag(path: &Path, options: &ReadOptions) -> Result<Option<String>> {
+    match options.xml_record_path {
+        Some(ref record_path) => Ok(Some(record_path.trim_matches('/').to_string())),
+        None => xml_reader::detect_record_tag(path, options.encoding.as_deref()),
+    }
+}
+
+/// The encoding CSV and XML files are decoded from: the configured one, else the
+/// detected one. `None` for other formats.
+pub fn text_encoding(path: &Path, options: &ReadOptions) -> Result<Option<&'static str>> {
+    let xml = match FileFormat::from_path(path) {
+        Some(FileFormat::Csv) => false,
+        Some(FileFormat::Xml) => true,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(
+        encoding::detect(path, options.encoding.as_deref(), xml)?.name(),
+    ))
+}
+
+/// The dialect `read_file` reads a CSV file with: configured settings, the rest sniffed.
+pub fn csv_dialect(path: &Path, options: &ReadOptions) -> Result<Dialect> {
+    let encoding = encoding::detect(path, options.encoding.as_deref(), false)?;
+    dialect::sniff(encoding::open_utf8(path, encoding)?, options)
+}
+
+/// Checks an `encoding` setting.
+pub fn validate_encoding(label: &str) -> Result<()> {
+    encoding::for_label(label).map(|_| ())
+}
@@ -1,13 +1,315 @@
 use anyhow::{Context, Result};
 use polars::prelude::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Write};
 use std::path::Path;
+use tempfile::NamedTempFile;
 
-pub fn read(path: &Path) -> Result<DataFrame> {
-    CsvReadOptions::default()
-        .with_has_header(true)
-        .with_infer_schema_length(Some(1000))
-        .try_into_reader_with_file_path(Some(path.into()))
+use super::dialect::{self, Dialect};
+use super::{encoding, BadLine, BadLinePolicy, ReadOptions};
+
+pub fn read(path: &Path, options: &ReadOptions) -> Result<(DataFrame, Vec<BadLine>)> {
+    // Polars only reads UTF-8, so other encodings are decoded to a temporary file first
+    let encoding = encoding::detect(path, options.encoding.as_deref(), false)?;
+    let transcoded = encoding::transcode_to_temp(path, encoding)?;
+    let path = transcoded.as_ref().map_or(path, |temp| temp.path());
+
+    let file = File::open(path).context("Failed to open CSV file")?;
+    let dialect = dialect::sniff(file, options)?;
+
+    let mut csv_options = CsvReadOptions::default()
+        .with_has_header(dialect.has_header)
+        .with_infer_schema_length(Some(options.infer_schema_rows))
+        .with_n_rows(options.max_rows)
+        .map_parse_options(|parse| {
+            parse
+                .with_separator(dialect.delimiter)
+                .with_quote_char(Some(dialect.quote_char))
+                .with_decimal_comma(dialect.decimal_comma)
+                .with_truncate_ragged_lines(options.csv_truncate_ragged_lines)
+                .with_missing_is_null(options.csv_missing_is_null)
+        });
+
+    if !options.text_columns.is_empty() {
+        let text_columns = text_schema(path, &csv_options, &options.text_columns)?;
+        csv_options = csv_options.with_schema_overwrite(text_columns);
+    }
+
+    if options.csv_bad_lines == BadLinePolicy::Error {
+        let df = csv_options
+            .try_into_reader_with_file_path(Some(path.into()))
+            .context("Failed to open CSV file")?
+            .finish()
+            .context("Failed to read CSV file")?;
+        return Ok((df, Vec::new()));
+    }
+
+    // The records the parser can read are copied to a temporary file, a record at a time
+    let file = File::open(path).context("Failed to open CSV file")?;
+    let mut clean = NamedTempFile::new().context("Failed to create temporary file")?;
+    let mut writer = BufWriter::new(clean.as_file_mut());
+    let bad_lines = split_bad_lines(BufReader::new(file), &mut writer, &dialect, options)
+        .context("Failed to read CSV file")?;
+    writer.flush().context("Failed to write temporary file")?;
+    drop(writer);
+
+    let df = csv_options
+        .try_into_reader_with_file_path(Some(clean.path().into()))
         .context("Failed to open CSV file")?
         .finish()
-        .context("Failed to read CSV file")
+        .context("Failed to read CSV file")?;
+
+    Ok((df, bad_lines))
+}
+
+/// A schema reading `text_columns` as text. Names the file does not have are left out;
+/// they are reported when the schema file is applied.
+fn text_schema(
+    path: &Path,
+    csv_options: &CsvReadOptions,
+    text_columns: &[String],
+) -> Result<Option<SchemaRef>> {
+    // Only the first line, so malformed lines further down do not get in the way
+    let mut first_line = Vec::new();
+    BufReader::new(File::open(path).context("Failed to open CSV file")?)
+        .read_until(b'\n', &mut first_line)
+        .context("Failed to read CSV file")?;
+
+    let header = csv_options
+        .clone()
+        .with_n_rows(Some(0))
+        .with_infer_schema_length(Some(0))
+        .into_reader_with_file_handle(Cursor::new(first_line))
+        .finish()
+        .context("Failed to read CSV header")?;
+
+    let fields: Vec<Field> = text_columns
+        .iter()
+        .filter(|name| header.get_column_index(name).is_some())
+        .map(|name| Field::new(name.as_str().into(), DataType::String))
+        .collect();
+
+    Ok((!fields.is_empty()).then(|| Arc::new(Schema::from_iter(fields))))
+}
+
+/// Scan state of one record: a line, or several when a quoted field spans lines.
+/// Quotes only open a quoted field at the start of a field; a doubled quote inside
+/// one is an escaped quote.
+struct RecordScan {
+    fields: usize,
+    lines: usize,
+    in_quotes: bool,
+    field_start: bool,
+    after_quote: bool,
+    problem: Option<&'static str>,
+}
+
+impl RecordScan {
+    fn new() -> Self {
+        Self {
+            fields: 1,
+            lines: 0,
+            in_quotes: false,
+            field_start: true,
+            after_quote: false,
+            problem: None,
+        }
+    }
+
+    /// Scans the next line of the record, including its line break. Returns whether
+    /// the record ends with it.
+    fn feed(&mut self, line: &[u8], dialect: &Dialect) -> bool {
+        let (delimiter, quote) = (dialect.delimiter, dialect.quote_char);
+        self.lines += 1;
+        let mut i = 0;
+
+        while i < line.len() {
+            let byte = line[i];
+            i += 1;
+
+            if self.in_quotes {
+                if byte == quote {
+                    if line.get(i) == Some(&quote) {
+                        i += 1;
+                    } else {
+                        self.in_quotes = false;
+                        self.after_quote = true;
+                    }
+                }
+                continue;
+            }
+
+            match byte {
+                b'\r' | b'\n' => {}
+                _ if byte == quote && self.field_start => {
+                    self.in_quotes = true;
+                    self.field_start = false;
+                }
+                _ if byte == delimiter => {
+                    self.fields += 1;
+                    self.field_start = true;
+                    self.after_quote = false;
+                }
+                _ => {
+                    if self.after_quote {
+                        self.problem.get_or_insert("text after closing quote");
+                    }
+                    self.field_start = false;
+                }
+            }
+        }
+
+        !self.in_quotes
+    }
+}
+
+/// Copies the records of `reader` to `clean`, except those the CSV parser would fail
+/// on: records with more fields than the first one, unless ragged lines are truncated,
+/// and badly quoted fields. Records with fewer fields are kept; their missing fields
+/// are null. Only one record is held in memory at a time.
+fn split_bad_lines(
+    mut reader: impl BufRead,
+    mut clean: impl Write,
+    dialect: &Dialect,
+    options: &ReadOptions,
+) -> std::io::Result<Vec<BadLine>> {
+    let mut bad_lines = Vec::new();
+    let mut expected_fields = None;
+    let mut line = 1;
+    let mut raw = Vec::new();
+
+    loop {
+        raw.clear();
+        let mut record = RecordScan::new();
+
+        loop {
+            let start = raw.len();
+            if reader.read_until(b'\n', &mut raw)? == 0 {
+                break;
+            }
+            if record.feed(&raw[start..], dialect) {
+                break;
+            }
+        }
+
+        if raw.is_empty() {
+            return Ok(bad_lines);
+        }
+        if record.in_quotes {
+            record.problem = Some("unterminated quote");
+        }
+
+        if raw.iter().all(|b| matches!(b, b'\r' | b'\n')) {
+            clean.write_all(&raw)?;
+            line += record.lines;
+            continue;
+        }
+
+        let expected = *expected_fields.get_or_insert(record.fields);
+        let problem = record.problem.map(str::to_string).or_else(|| {
+            (record.fields > expected && !options.csv_truncate_ragged_lines)
+                .then(|| format!("{} fields, expected {}", record.fields, expected))
+        });
+
+        match problem {
+            Some(reason) => bad_lines.push(BadLine {
+                line,
+                content: String::from_utf8_lossy(&raw)
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string(),
+                reason,
+            }),
+            None => clean.write_all(&raw)?,
+        }
+
+        line += record.lines;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comma() -> Dialect {
+        Dialect {
+            delimiter: b',',
+            quote_char: b'"',
+            has_header: true,
+            decimal_comma: false,
+        }
+    }
+
+    fn split(input: &str, options: &ReadOptions) -> (String, Vec<BadLine>) {
+        let mut clean = Vec::new();
+        let bad_lines = split_bad_lines(input.as_bytes(), &mut clean, &comma(), options).unwrap();
+        (String::from_utf8(clean).unwrap(), bad_lines)
+    }
+
+    #[test]
+    fn good_records_pass_through_unchanged() {
+        let input = "a,b,c\r\n1,\"x, y\",3\n\n4,\"multi\nline\",6\n7,8\n9,\"say \"\"hi\"\"\",10";
+        let (clean, bad_lines) = split(input, &ReadOptions::default());
+
+        assert_eq!(clean, input);
+        assert!(bad_lines.is_empty());
+    }
+
+    #[test]
+    fn bad_records_are_moved_out_with_their_line_numbers() {
+        let input = "a,b\n1,2\n3,4,5\n\"x\"y,6\n7,\"two\nlines\",8\n9,10\n";
+        let (clean, bad_lines) = split(input, &ReadOptions::default());
+
+        assert_eq!(clean, "a,b\n1,2\n9,10\n");
+        let found: Vec<_> = bad_lines
+            .iter()
+            .map(|b| (b.line, b.content.as_str(), b.reason.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (3, "3,4,5", "3 fields, expected 2"),
+                (4, "\"x\"y,6", "text after closing quote"),
+                (5, "7,\"two\nlines\",8", "3 fields, expected 2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_quote_takes_the_rest_of_the_file() {
+        let (clean, bad_lines) = split("a,b\n1,\"open\n2,3\n", &ReadOptions::default());
+
+        assert_eq!(clean, "a,b\n");
+        assert_eq!(bad_lines.len(), 1);
+        assert_eq!(bad_lines[0].line, 2);
+        assert_eq!(bad_lines[0].reason, "unterminated quote");
+    }
+
+    #[test]
+    fn ragged_lines_are_kept_when_truncated() {
+        let options = ReadOptions {
+            csv_truncate_ragged_lines: true,
+            ..Default::default()
+        };
+        let (clean, bad_lines) = split("a,b\n1,2,3\n4\n", &options);
+
+        assert_eq!(clean, "a,b\n1,2,3\n4\n");
+        assert!(bad_lines.is_empty());
+    }
+
+    #[test]
+    fn skipped_lines_do_not_reach_the_parser() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.csv");
+        std::fs::write(&path, "id,amount\n1,10\n2,20,extra\n3,30\n").unwrap();
+
+        let options = ReadOptions {
+            csv_bad_lines: BadLinePolicy::Skip,
+            ..Default::default()
+        };
+        let (df, bad_lines) = read(&path, &options).unwrap();
+
+        assert_eq!(df.height(), 2);
+        assert_eq!(bad_lines.len(), 1);
+        assert_eq!(bad_lines[0].line, 3);
+    }
 }
@@ -0,0 +1,347 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::io::Read;
+
+use super::ReadOptions;
+
+/// Bytes of the file the dialect is sniffed from.
+const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Lines of the sample looked at.
+const SAMPLE_LINES: usize = 100;
+
+/// Delimiters tried, in order of preference when they fit the sample equally well.
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// How a CSV file is written. Settings given in the config are used as they are;
+/// the others are sniffed from the start of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Dialect {
+    #[serde(serialize_with = "as_char")]
+    pub delimiter: u8,
+    #[serde(serialize_with = "as_char")]
+    pub quote_char: u8,
+    pub has_header: bool,
+    /// Numbers are written as `1,5` instead of `1.5`
+    pub decimal_comma: bool,
+}
+
+/// Reads the start of `reader`, which must be UTF-8, and fills in the dialect settings
+/// `options` leaves unset.
+pub fn sniff(reader: impl Read, options: &ReadOptions) -> Result<Dialect> {
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    reader
+        .take(SAMPLE_SIZE as u64)
+        .read_to_end(&mut sample)
+        .context("Failed to read CSV file")?;
+
+    let text = String::from_utf8_lossy(&sample);
+    let mut lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    // A full sample may end in the middle of a line
+    if sample.len() == SAMPLE_SIZE && lines.len() > 1 {
+        lines.pop();
+    }
+    lines.truncate(SAMPLE_LINES);
+
+    let quote_char = options
+        .csv_quote_char
+        .unwrap_or_else(|| sniff_quote(&lines));
+    // Polars cannot read decimal commas from comma-separated files
+    let comma_allowed = options.csv_decimal_comma != Some(true);
+    let delimiter = options
+        .csv_delimiter
+        .unwrap_or_else(|| sniff_delimiter(&lines, quote_char, comma_allowed));
+    if delimiter == b',' && !comma_allowed {
+        return Err(anyhow!(
+            "csv_decimal_comma cannot be used with a ',' delimiter"
+        ));
+    }
+
+    let rows: Vec<Vec<String>> = lines
+        .iter()
+        .map(|line| split_fields(line, delimiter, quote_char))
+        .collect();
+
+    let decimal_comma = options
+        .csv_decimal_comma
+        .unwrap_or_else(|| delimiter != b',' && sniff_decimal_comma(&rows));
+    let has_header = options
+        .csv_has_header
+        .unwrap_or_else(|| sniff_header(&rows, decimal_comma));
+
+    Ok(Dialect {
+        delimiter,
+        quote_char,
+        has_header,
+        decimal_comma,
+    })
+}
+
+/// `'` when it opens more fields than `"` does, otherwise `"`.
+fn sniff_quote(lines: &[&str]) -> u8 {
+    let opening = |quote: u8| -> usize {
+        lines
+            .iter()
+            .map(|line| {
+                let bytes = line.as_bytes();
+                bytes
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, b)| {
+                        **b == quote && (*i == 0 || DELIMITERS.contains(&bytes[i - 1]))
+                    })
+                    .count()
+            })
+            .sum()
+    };
+
+    if opening(b'\'') > opening(b'"') {
+        b'\''
+    } else {
+        b'"'
+    }
+}
+
+/// The delimiter that splits the most lines, the first one included, into the same
+/// number of fields, more than one. Ties go to the wider split, then to the earlier
+/// entry in `DELIMITERS`. A single-column file gets one that does not occur in it.
+/// `,` is only tried when `comma_allowed`.
+fn sniff_delimiter(lines: &[&str], quote_char: u8, comma_allowed: bool) -> u8 {
+    let candidates = || {
+        DELIMITERS
+            .into_iter()
+            .filter(move |d| comma_allowed || *d != b',')
+    };
+    let unused = candidates()
+        .find(|d| !lines.iter().any(|line| line.as_bytes().contains(d)))
+        .unwrap_or(if comma_allowed { b',' } else { b';' });
+    let mut best = (unused, 0, 0);
+
+    for delimiter in candidates() {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for line in lines {
+            *counts
+                .entry(split_fields(line, delimiter, quote_char).len())
+                .or_default() += 1;
+        }
+
+        let Some((fields, lines_matching)) = counts
+            .into_iter()
+            .filter(|(fields, _)| *fields > 1)
+            .max_by_key(|(fields, count)| (*count, *fields))
+        else {
+            continue;
+        };
+
+        let header_fields = lines
+            .first()
+            .map(|l| split_fields(l, delimiter, quote_char).len());
+        if header_fields != Some(fields) {
+            continue;
+        }
+
+        if (lines_matching, fields) > (best.1, best.2) {
+            best = (delimiter, lines_matching, fields);
+        }
+    }
+
+    best.0
+}
+
+/// Decimal commas when some values look like `1,5` and none like `1.5`. Values such
+/// as `1.234` may be thousands with decimal commas, so they count for neither.
+fn sniff_decimal_comma(rows: &[Vec<String>]) -> bool {
+    let values = || rows.iter().flatten().map(String::as_str);
+
+    values().any(|v| is_decimal(v, ','))
+        && !values().any(|v| is_decimal(v, '.') && !is_thousands(v, '.'))
+}
+
+/// A header when the first row has text where the rest of the column holds numbers.
+/// Files without numeric columns are taken to have a header.
+fn sniff_header(rows: &[Vec<String>], decimal_comma: bool) -> bool {
+    let Some((first, data)) = rows.split_first() else {
+        return true;
+    };
+    if data.is_empty() {
+        return true;
+    }
+
+    let is_number =
+        |value: &str| value.parse::<f64>().is_ok() || (decimal_comma && is_decimal(value, ','));
+
+    let mut votes = 0i32;
+    for (index, name) in first.iter().enumerate() {
+        let mut values = data
+            .iter()
+            .filter_map(|row| row.get(index))
+            .filter(|v| !v.is_empty())
+            .peekable();
+
+        if values.peek().is_none() || !values.all(|v| is_number(v)) {
+            continue;
+        }
+        votes += if is_number(name) { -1 } else { 1 };
+    }
+
+    votes >= 0
+}
+
+/// Splits one line into unquoted, trimmed fields.
+fn split_fields(line: &str, delimiter: u8, quote_char: u8) -> Vec<String> {
+    let (delimiter, quote_char) = (delimiter as char, quote_char as char);
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == quote_char {
+                if chars.peek() == Some(&quote_char) {
+                    field.push(c);
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == quote_char && field.trim().is_empty() {
+            in_quotes = true;
+        } else if c == delimiter {
+            fields.push(field.trim().to_string());
+            field.clear();
+        } else {
+            field.push(c);
+        }
+    }
+    fields.push(field.trim().to_string());
+
+    fields
+}
+
+/// An optional sign, digits, `separator`, digits.
+fn is_decimal(value: &str, separator: char) -> bool {
+    let value = value.strip_prefix(['-', '+']).unwrap_or(value);
+
+    value
+        .split_once(separator)
+        .is_some_and(|(whole, fraction)| {
+            !whole.is_empty()
+                && !fraction.is_empty()
+                && whole.chars().all(|c| c.is_ascii_digit())
+                && fraction.chars().all(|c| c.is_ascii_digit())
+        })
+}
+
+/// An optional sign, then 1 to 3 digits followed by groups of `separator` and 3 digits.
+fn is_thousands(value: &str, separator: char) -> bool {
+    let value = value.strip_prefix(['-', '+']).unwrap_or(value);
+    let mut groups = value.split(separator);
+    let first = groups.next().unwrap_or_default();
+    let digits = |group: &str| group.chars().all(|c| c.is_ascii_digit());
+
+    (1..=3).contains(&first.len())
+        && digits(first)
+        && value.contains(separator)
+        && groups.all(|group| group.len() == 3 && digits(group))
+}
+
+fn as_char<S: Serializer>(byte: &u8, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_char(*byte as char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sniff_str(text: &str) -> Dialect {
+        sniff(text.as_bytes(), &ReadOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn delimiter_is_the_one_that_splits_lines_evenly() {
+        assert_eq!(sniff_str("a,b,c\n1,2,3\n4,5,6\n").delimiter, b',');
+        assert_eq!(sniff_str("a;b\n1,5;2\n3;4,25\n").delimiter, b';');
+        assert_eq!(sniff_str("a\tb\n1\t2\n").delimiter, b'\t');
+        assert_eq!(sniff_str("a|b|c\n\"x|y\"|2|3\n").delimiter, b'|');
+    }
+
+    #[test]
+    fn single_column_gets_an_unused_delimiter() {
+        assert_eq!(sniff_str("name\nJones, Ann\nSmith, Bob\n").delimiter, b';');
+    }
+
+    #[test]
+    fn single_quotes_are_sniffed() {
+        let dialect = sniff_str("'a';'b'\n'x;y';'2'\n");
+        assert_eq!(dialect.quote_char, b'\'');
+        assert_eq!(dialect.delimiter, b';');
+    }
+
+    #[test]
+    fn header_is_text_above_numbers() {
+        assert!(sniff_str("id,amount\n1,10\n2,20\n").has_header);
+        assert!(!sniff_str("1,10\n2,20\n3,30\n").has_header);
+        assert!(sniff_str("name,city\nAnn,Oslo\n").has_header);
+    }
+
+    #[test]
+    fn decimal_comma_needs_comma_decimals_and_no_dot_decimals() {
+        assert!(sniff_str("id;amount\n1;1,5\n2;2,25\n").decimal_comma);
+        assert!(!sniff_str("id;amount\n1;1,5\n2;2.25\n").decimal_comma);
+        assert!(!sniff_str("id,amount\n1,\"1,5\"\n").decimal_comma);
+    }
+
+    #[test]
+    fn thousands_do_not_count_as_dot_decimals() {
+        let dialect = sniff_str("id;amount\n1;1.234\n2;12,5\n3;-1.234.567\n");
+        assert!(dialect.decimal_comma);
+        assert!(dialect.has_header);
+
+        assert!(!sniff_str("id;amount\n1;1.2345\n2;12,5\n").decimal_comma);
+    }
+
+    #[test]
+    fn configured_settings_are_kept() {
+        let options = ReadOptions {
+            csv_delimiter: Some(b'|'),
+            csv_has_header: Some(false),
+            csv_decimal_comma: Some(true),
+            ..Default::default()
+        };
+        let dialect = sniff("a,b\n1,2\n".as_bytes(), &options).unwrap();
+
+        assert_eq!(dialect.delimiter, b'|');
+        assert!(!dialect.has_header);
+        assert!(dialect.decimal_comma);
+    }
+
+    #[test]
+    fn decimal_comma_rules_out_the_comma_delimiter() {
+        let sniffed = ReadOptions {
+            csv_decimal_comma: Some(true),
+            ..Default::default()
+        };
+        let dialect = sniff("a;b\n1,5;2\n".as_bytes(), &sniffed).unwrap();
+        assert_eq!(dialect.delimiter, b';');
+
+        let configured = ReadOptions {
+            csv_delimiter: Some(b','),
+            ..sniffed
+        };
+        assert!(sniff("a,b\n1,2\n".as_bytes(), &configured).is_err());
+    }
+
+    #[test]
+    fn thousands_pattern() {
+        for value in ["1.234", "-12.345", "123.456.789"] {
+            assert!(is_thousands(value, '.'), "{}", value);
+        }
+        for value in ["1234", "1.23", "1234.567", "1.2345", ".123", "1..234"] {
+            assert!(!is_thousands(value, '.'), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use anyhow::{anyhow, Context, Result};
+use encoding_rs::{CoderResult, Decoder, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// Bytes looked at to detect the encoding.
+const SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Size of the decoded chunks handed to the parser.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Resolves an `encoding` setting such as `windows-1252` or `latin1` (WHATWG labels).
+pub fn for_label(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| anyhow!("Unknown encoding: {}", label))
+}
+
+/// The configured encoding, or the detected one: a byte order mark first, then UTF-16
+/// when every other byte is zero, then for XML the declaration, then UTF-8 when the
+/// whole file is valid UTF-8, otherwise Windows-1252 (which covers ISO-8859-1). The
+/// first bytes are sampled; only the UTF-8 check reads on when the sample passes.
+pub fn detect(path: &Path, configured: Option<&str>, xml: bool) -> Result<&'static Encoding> {
+    if let Some(label) = configured {
+        return for_label(label);
+    }
+
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    File::open(path)
+        .context("Failed to open file")?
+        .take(SAMPLE_SIZE as u64)
+        .read_to_end(&mut sample)
+        .context("Failed to read file")?;
+
+    if let Some((encoding, _)) = Encoding::for_bom(&sample) {
+        return Ok(encoding);
+    }
+
+    if let Some(utf16) = utf16_without_bom(&sample) {
+        return Ok(utf16);
+    }
+
+    if xml {
+        // A declaration claiming UTF-8 is not trusted when the bytes say otherwise
+        if let Some(encoding) = xml_declaration(&sample).filter(|e| *e != UTF_8) {
+            return Ok(encoding);
+        }
+    }
+
+    Ok(if is_utf8_file(path, &sample)? {
+        UTF_8
+    } else {
+        WINDOWS_1252
+    })
+}
+
+/// Opens `path` as a stream of UTF-8, decoding on the fly unless it already is UTF-8.
+pub fn open_utf8(path: &Path, encoding: &'static Encoding) -> Result<BufReader<Box<dyn Read>>> {
+    let file = File::open(path).context("Failed to open file")?;
+
+    let reader: Box<dyn Read> = if encoding == UTF_8 {
+        Box::new(file)
+    } else {
+        Box::new(Transcoder::new(file, encoding))
+    };
+
+    Ok(BufReader::with_capacity(BUFFER_SIZE, reader))
+}
+
+/// Copies `path` decoded to UTF-8 into a temporary file, for parsers that need a file.
+/// This writes a full copy of the file. Returns `None` when the file is UTF-8 already.
+pub fn transcode_to_temp(
+    path: &Path,
+    encoding: &'static Encoding,
+) -> Result<Option<NamedTempFile>> {
+    if encoding == UTF_8 {
+        return Ok(None);
+    }
+
+    let mut temp = NamedTempFile::new().context("Failed to create temporary file")?;
+    io::copy(&mut open_utf8(path, encoding)?, &mut temp)
+        .with_context(|| format!("Failed to decode file as {}", encoding.name()))?;
+
+    Ok(Some(temp))
+}
+
+/// Decodes a byte stream to UTF-8 chunk by chunk. A byte order mark is removed;
+/// bytes that are invalid in the encoding become U+FFFD.
+struct Transcoder<R> {
+    inner: R,
+    decoder: Decoder,
+    input: Vec<u8>,
+    start: usize,
+    end: usize,
+    /// Decoded bytes a too-small read buffer had no room for
+    pending: Vec<u8>,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R: Read> Transcoder<R> {
+    fn new(inner: R, encoding: &'static Encoding) -> Self {
+        Self {
+            inner,
+            decoder: encoding.new_decoder_with_bom_removal(),
+            input: vec![0; BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            pending: Vec::new(),
+            eof: false,
+            finished: false,
+        }
+    }
+}
+
+impl<R: Read> Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.pending.is_empty() {
+            let n = self.pending.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            return Ok(n);
+        }
+
+        if self.finished || buf.is_empty() {
+            return Ok(0);
+        }
+
+        // decode_to_utf8 needs room for at least one character
+        if buf.len() < 4 {
+            let mut small = [0u8; 4];
+            let n = self.read(&mut small)?;
+            self.pending.extend_from_slice(&small[..n]);
+            return self.read(buf);
+        }
+
+        loop {
+            if self.start == self.end && !self.eof {
+                self.start = 0;
+                self.end = self.inner.read(&mut self.input)?;
+                self.eof = self.end == 0;
+            }
+
+            let (result, read, written, _) =
+                self.decoder
+                    .decode_to_utf8(&self.input[self.start..self.end], buf, self.eof);
+            self.start += read;
+
+            if self.eof && result == CoderResult::InputEmpty {
+                self.finished = true;
+                return Ok(written);
+            }
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+    }
+}
+
+/// Whether the file `sample` was taken from is valid UTF-8. The rest of the file is
+/// only read when the sample passes and is not the whole file.
+fn is_utf8_file(path: &Path, sample: &[u8]) -> Result<bool> {
+    if !is_utf8(sample) {
+        return Ok(false);
+    }
+    if sample.len() < SAMPLE_SIZE {
+        return Ok(true);
+    }
+
+    let file = File::open(path).context("Failed to open file")?;
+    is_utf8_stream(file).context("Failed to read file")
+}
+
+/// Validates a whole stream as UTF-8, chunk by chunk.
+fn is_utf8_stream(mut reader: impl Read) -> io::Result<bool> {
+    let mut buf = vec![0; BUFFER_SIZE];
+    let mut carried = 0;
+
+    loop {
+        let n = reader.read(&mut buf[carried..])?;
+        let end = carried + n;
+        if n == 0 {
+            return Ok(carried == 0);
+        }
+
+        match std::str::from_utf8(&buf[..end]) {
+            Ok(_) => carried = 0,
+            // A character cut off at the end of the chunk is completed by the next read
+            Err(e) if e.error_len().is_none() => {
+                let valid = e.valid_up_to();
+                buf.copy_within(valid..end, 0);
+                carried = end - valid;
+            }
+            Err(_) => return Ok(false),
+        }
+    }
+}
+
+fn is_utf8(sample: &[u8]) -> bool {
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        // The sample may end in the middle of a character
+        Err(e) => e.error_len().is_none() && sample.len() == SAMPLE_SIZE,
+    }
+}
+
+/// UTF-16 text without a byte order mark, recognised by its zero high bytes
+/// in mostly-ASCII content.
+fn utf16_without_bom(sample: &[u8]) -> Option<&'static Encoding> {
+    if sample.len() < 4 {
+        return None;
+    }
+
+    let pairs = sample.len() / 2;
+    let zeros_at = |offset: usize| {
+        sample
+            .chunks_exact(2)
+            .filter(|pair| pair[offset] == 0)
+            .count()
+    };
+
+    if zeros_at(1) * 10 >= pairs * 9 && zeros_at(0) == 0 {
+        Some(UTF_16LE)
+    } else if zeros_at(0) * 10 >= pairs * 9 && zeros_at(1) == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// The encoding named in `<?xml version="1.0" encoding="..."?>`.
+fn xml_declaration(sample: &[u8]) -> Option<&'static Encoding> {
+    let head = &sample[..sample.len().min(256)];
+    let end = head.windows(2).position(|w| w == b"?>")?;
+    let declaration = String::from_utf8_lossy(&head[..end]);
+    let declaration = declaration.trim_start().strip_prefix("<?xml")?;
+
+    let value = declaration.split("encoding").nth(1)?;
+    let value = value.trim_start().strip_prefix('=')?.trim_start();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let label = value[1..].split(quote).next()?;
+
+    Encoding::for_label(label.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_bytes(bytes: &[u8], xml: bool) -> &'static Encoding {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), bytes).unwrap();
+        detect(file.path(), None, xml).unwrap()
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    /// Reads everything from `reader` with a buffer of `size` bytes.
+    fn read_with(mut reader: impl Read, size: usize) -> String {
+        let mut out = Vec::new();
+        let mut buf = vec![0; size];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                return String::from_utf8(out).unwrap();
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[test]
+    fn configured_label_wins() {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "plain").unwrap();
+
+        assert_eq!(
+            detect(file.path(), Some(" latin1 "), false).unwrap(),
+            WINDOWS_1252
+        );
+        assert!(detect(file.path(), Some("no-such-encoding"), false).is_err());
+    }
+
+    #[test]
+    fn bom_and_utf16_are_detected() {
+        assert_eq!(detect_bytes(b"\xEF\xBB\xBFid,name\n", false), UTF_8);
+        assert_eq!(detect_bytes(b"\xFE\xFF\0i\0d", false), UTF_16BE);
+        assert_eq!(detect_bytes(&utf16le("id,name\n1,a\n"), false), UTF_16LE);
+    }
+
+    #[test]
+    fn utf8_and_latin1_are_told_apart() {
+        assert_eq!(detect_bytes("name\nJosé\n".as_bytes(), false), UTF_8);
+        assert_eq!(detect_bytes(b"name\nJos\xE9\n", false), WINDOWS_1252);
+    }
+
+    #[test]
+    fn invalid_utf8_after_the_sample_falls_back() {
+        let mut bytes = "é,".repeat(SAMPLE_SIZE).into_bytes();
+        bytes.extend_from_slice(b"\nJos\xE9\n");
+        assert_eq!(detect_bytes(&bytes, false), WINDOWS_1252);
+
+        // A character split by the sample or chunk boundary is still valid
+        let bytes = format!("a{}", "é".repeat(SAMPLE_SIZE)).into_bytes();
+        assert_eq!(detect_bytes(&bytes, false), UTF_8);
+    }
+
+    #[test]
+    fn xml_declaration_is_used_unless_the_bytes_disagree() {
+        let latin2 = b"<?xml version=\"1.0\" encoding='ISO-8859-2'?><r/>";
+        assert_eq!(detect_bytes(latin2, true).name(), "ISO-8859-2");
+        assert_eq!(detect_bytes(latin2, false), UTF_8);
+
+        let lying = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><r>Jos\xE9</r>";
+        assert_eq!(detect_bytes(lying, true), WINDOWS_1252);
+    }
+
+    #[test]
+    fn transcoder_decodes_with_any_buffer_size() {
+        let text = "id;name\n1;Zoë\n2;日本\n".repeat(200);
+        let mut source = vec![0xFF, 0xFE];
+        source.extend(utf16le(&text));
+
+        for size in [1, 2, 3, 4, 7, BUFFER_SIZE * 2] {
+            let decoded = read_with(Transcoder::new(source.as_slice(), UTF_16LE), size);
+            assert_eq!(decoded, text, "buffer of {} bytes", size);
+        }
+    }
+
+    #[test]
+    fn transcoder_replaces_invalid_bytes() {
+        // A lone surrogate, then half a code unit at the end
+        let source = [b'a', 0, 0x00, 0xD8, b'b', 0, b'c'];
+        let decoded = read_with(Transcoder::new(&source[..], UTF_16LE), 16);
+        assert_eq!(decoded, "a\u{FFFD}b\u{FFFD}");
+    }
+}
@@ -4,7 +4,7 @@ use polars::prelude::*;
 use std::io::BufReader;
 use std::path::Path;
 
-pub fn read(path: &Path, sheet_name: Option<&str>) -> Result<DataFrame> {
+pub fn read(path: &Path, sheet_name: Option<&str>, max_rows: Option<usize>) -> Result<DataFrame> {
     let mut workbook = open_workbook_auto(path).context("Failed to open Excel/ODS file")?;
 
     let sheet = get_sheet_name(&workbook, sheet_name)?;
@@ -17,12 +17,26 @@ pub fn read(path: &Path, sheet_name: Option<&str>) -> Result<DataFrame> {
         return Err(anyhow!("Empty sheet"));
     }
 
+    // The first row holds the headers
+    let rows = max_rows.map_or(rows, |max| rows.min(max.saturating_add(1)));
+
     let headers = extract_headers(&range, cols);
     let columns = build_columns(&range, &headers, rows, cols);
 
     DataFrame::new(columns).context("Failed to create DataFrame from Excel")
 }
 
+/// The sheet `read` takes: `sheet_name`, else the first sheet.
+pub fn resolve_sheet(path: &Path, sheet_name: Option<&str>) -> Result<String> {
+    let workbook = open_workbook_auto(path).context("Failed to open Excel/ODS file")?;
+    get_sheet_name(&workbook, sheet_name)
+}
+
+pub fn sheet_names(path: &Path) -> Result<Vec<String>> {
+    let workbook = open_workbook_auto(path).context("Failed to open Excel/ODS file")?;
+    Ok(workbook.sheet_names())
+}
+
 fn get_sheet_name(workbook: &Sheets<BufReader<std::fs::File>>, name: Option<&str>) -> Result<String> {
     match name {
         Some(n) => Ok(n.to_string()),
@@ -1,14 +1,40 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use polars::prelude::*;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
 use std::path::Path;
 
-pub fn read(path: &Path) -> Result<DataFrame> {
+pub fn read(path: &Path, pointer: Option<&str>) -> Result<DataFrame> {
     let file = File::open(path).context("Failed to open JSON file")?;
     let reader = BufReader::new(file);
 
-    JsonReader::new(reader)
+    match pointer {
+        Some(pointer) => read_at_pointer(reader, pointer),
+        None => JsonReader::new(reader)
+            .finish()
+            .context("Failed to read JSON file"),
+    }
+}
+
+/// Reads the array found at a JSON pointer (RFC 6901), e.g. `/data/items`.
+fn read_at_pointer(reader: BufReader<File>, pointer: &str) -> Result<DataFrame> {
+    let document: serde_json::Value =
+        serde_json::from_reader(reader).context("Failed to parse JSON file")?;
+
+    let records = document
+        .pointer(pointer)
+        .ok_or_else(|| anyhow!("JSON pointer not found: {}", pointer))?;
+
+    if !records.is_array() {
+        return Err(anyhow!(
+            "JSON pointer {} does not point to an array",
+            pointer
+        ));
+    }
+
+    let bytes = serde_json::to_vec(records).context("Failed to extract JSON records")?;
+
+    JsonReader::new(Cursor::new(bytes))
         .finish()
         .context("Failed to read JSON file")
 }
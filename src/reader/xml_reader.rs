@@ -3,50 +3,105 @@ use polars::prelude::*;
 use quick_xml::events::Event;
 use quick_xml::Reader;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
+use std::io::BufRead;
 use std::path::Path;
 
-pub fn read(path: &Path) -> Result<DataFrame> {
-    let file = File::open(path).context("Failed to open XML file")?;
-    let reader = BufReader::new(file);
+use super::{encoding, ReadOptions};
 
-    let records = parse_xml(reader)?;
+/// Field values of one record, by element name.
+type Record = HashMap<String, String>;
+
+pub fn read(path: &Path, options: &ReadOptions) -> Result<DataFrame> {
+    let encoding = encoding::detect(path, options.encoding.as_deref(), true)?;
+    let reader = encoding::open_utf8(path, encoding).context("Failed to open XML file")?;
+
+    let (columns, records) = parse_xml(
+        reader,
+        options.xml_record_path.as_deref(),
+        options.max_rows,
+    )?;
 
     if records.is_empty() {
         return Err(anyhow!("No records found in XML"));
     }
 
-    records_to_dataframe(records)
+    records_to_dataframe(&columns, &records)
 }
 
-fn parse_xml(reader: BufReader<File>) -> Result<Vec<HashMap<String, String>>> {
+/// Finds the first element below the root, which `parse_xml` takes as the record tag.
+pub fn detect_record_tag(path: &Path, encoding: Option<&str>) -> Result<Option<String>> {
+    let encoding = encoding::detect(path, encoding, true)?;
+    let reader = encoding::open_utf8(path, encoding).context("Failed to open XML file")?;
+    let mut xml_reader = Reader::from_reader(reader);
+
+    let mut buf = Vec::new();
+    let mut depth = 0;
+
+    loop {
+        match xml_reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                depth += 1;
+                if depth == 2 {
+                    return Ok(Some(String::from_utf8_lossy(e.name().as_ref()).to_string()));
+                }
+            }
+            Ok(Event::End(_)) => depth -= 1,
+            Ok(Event::Eof) => return Ok(None),
+            Err(e) => {
+                return Err(anyhow!(
+                    "Failed to read XML at position {}: {:?}",
+                    xml_reader.error_position(),
+                    e
+                ))
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Collects one record per record element, with its child elements as fields.
+///
+/// With a `record_path` such as `catalog/book`, records are the elements at exactly that
+/// path. Otherwise the first element below the root is taken as the record tag.
+/// Field names are returned in the order they first appear in the document. Parsing
+/// stops after `max_records` records.
+fn parse_xml(
+    reader: impl BufRead,
+    record_path: Option<&str>,
+    max_records: Option<usize>,
+) -> Result<(Vec<String>, Vec<Record>)> {
     let mut xml_reader = Reader::from_reader(reader);
     xml_reader.config_mut().trim_text(true);
 
+    let target: Option<Vec<&str>> = record_path.map(|p| {
+        p.trim_matches('/')
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .collect()
+    });
+
     let mut buf = Vec::new();
     let mut records = Vec::new();
-    let mut current_record: HashMap<String, String> = HashMap::new();
+    let mut columns: Vec<String> = Vec::new();
+    let mut current_record = Record::new();
     let mut current_element: Option<String> = None;
     let mut record_tag: Option<String> = None;
-    let mut depth = 0;
+    let mut record_depth: Option<usize> = None;
+    let mut stack: Vec<String> = Vec::new();
 
     loop {
         match xml_reader.read_event_into(&mut buf) {
             Ok(Event::Start(ref e)) => {
-                depth += 1;
                 let tag_name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                stack.push(tag_name.clone());
 
-                match depth {
-                    2 => {
-                        if record_tag.is_none() {
-                            record_tag = Some(tag_name.clone());
-                        }
-                        if Some(&tag_name) == record_tag.as_ref() {
-                            current_record = HashMap::new();
-                        }
+                match record_depth {
+                    None if is_record_start(&stack, target.as_deref(), &mut record_tag) => {
+                        current_record = Record::new();
+                        record_depth = Some(stack.len());
                     }
-                    3 => current_element = Some(tag_name),
+                    Some(depth) if stack.len() == depth + 1 => current_element = Some(tag_name),
                     _ => {}
                 }
             }
@@ -54,23 +109,28 @@ fn parse_xml(reader: BufReader<File>) -> Result<Vec<HashMap<String, String>>> {
                 if let Some(ref elem) = current_element {
                     let text = e.unescape().unwrap_or_default().to_string();
                     if !text.is_empty() {
+                        if !columns.contains(elem) {
+                            columns.push(elem.clone());
+                        }
                         current_record.insert(elem.clone(), text);
                     }
                 }
             }
-            Ok(Event::End(ref e)) => {
-                let tag_name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-
-                if depth == 2
-                    && Some(&tag_name) == record_tag.as_ref()
-                    && !current_record.is_empty()
-                {
-                    records.push(current_record.clone());
-                }
-                if depth == 3 {
-                    current_element = None;
+            Ok(Event::End(_)) => {
+                match record_depth {
+                    Some(depth) if stack.len() == depth => {
+                        if !current_record.is_empty() {
+                            records.push(std::mem::take(&mut current_record));
+                            if max_records == Some(records.len()) {
+                                break;
+                            }
+                        }
+                        record_depth = None;
+                    }
+                    Some(depth) if stack.len() == depth + 1 => current_element = None,
+                    _ => {}
                 }
-                depth -= 1;
+                stack.pop();
             }
             Ok(Event::Eof) => break,
             Err(e) => {
@@ -85,12 +145,23 @@ fn parse_xml(reader: BufReader<File>) -> Result<Vec<HashMap<String, String>>> {
         buf.clear();
     }
 
-    Ok(records)
+    Ok((columns, records))
 }
 
-fn records_to_dataframe(records: Vec<HashMap<String, String>>) -> Result<DataFrame> {
-    let columns = collect_columns(&records);
+fn is_record_start(stack: &[String], target: Option<&[&str]>, record_tag: &mut Option<String>) -> bool {
+    match target {
+        Some(path) => stack.iter().map(String::as_str).eq(path.iter().copied()),
+        None => {
+            if stack.len() != 2 {
+                return false;
+            }
+            let tag = &stack[1];
+            record_tag.get_or_insert_with(|| tag.clone()) == tag
+        }
+    }
+}
 
+fn records_to_dataframe(columns: &[String], records: &[Record]) -> Result<DataFrame> {
     let column_vec: Vec<Column> = columns
         .iter()
         .map(|col| {
@@ -105,17 +176,3 @@ fn records_to_dataframe(records: Vec<HashMap<String, String>>) -> Result<DataFra
 
     DataFrame::new(column_vec).context("Failed to create DataFrame from XML")
 }
-
-fn collect_columns(records: &[HashMap<String, String>]) -> Vec<String> {
-    let mut columns = Vec::new();
-
-    for record in records {
-        for key in record.keys() {
-            if !columns.contains(key) {
-                columns.push(key.clone());
-            }
-        }
-    }
-
-    columns
-}
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cli::OutputFormat;
+use crate::converter::ConversionResult;
+use crate::processor::ProcessStats;
+
+/// Machine-readable summary of a `batch` or `convert` run.
+#[derive(Debug, Serialize)]
+pub struct RunReport<'a> {
+    pub files: &'a [FileReport],
+    pub totals: Totals,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub status: FileStatus,
+    pub outputs: Vec<PathBuf>,
+    pub format: Option<&'static str>,
+    pub rows: Option<usize>,
+    pub columns: Option<usize>,
+    pub input_bytes: Option<u64>,
+    pub output_bytes: Option<u64>,
+    pub duration_ms: u64,
+    pub schema: Vec<ColumnReport>,
+    pub rejected: Option<usize>,
+    pub rejects: Option<PathBuf>,
+    pub skipped_lines: Option<usize>,
+    pub bad_lines: Option<PathBuf>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColumnReport {
+    pub name: String,
+    pub dtype: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Totals {
+    pub files: usize,
+    pub processed: usize,
+    pub errors: usize,
+    pub not_attempted: usize,
+    pub rows: u64,
+    pub rejected: u64,
+    pub skipped_lines: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub compression_ratio: Option<f64>,
+    pub elapsed_ms: u64,
+}
+
+impl FileReport {
+    pub fn converted(input: &Path, result: &ConversionResult, duration: Duration) -> Self {
+        Self {
+            input: input.to_path_buf(),
+            status: FileStatus::Ok,
+            outputs: result.outputs.clone(),
+            format: Some(result.format.name()),
+            rows: Some(result.rows),
+            columns: Some(result.columns),
+            input_bytes: Some(result.input_bytes),
+            output_bytes: Some(result.output_bytes),
+            duration_ms: duration.as_millis() as u64,
+            schema: result
+                .schema
+                .iter()
+                .map(|(name, dtype)| ColumnReport {
+                    name: name.clone(),
+                    dtype: dtype.clone(),
+                })
+                .collect(),
+            rejected: Some(result.rejected),
+            rejects: result.rejects.clone(),
+            skipped_lines: Some(result.skipped_lines),
+            bad_lines: result.bad_lines.clone(),
+            error: None,
+        }
+    }
+
+    pub fn failed(input: &Path, error: &anyhow::Error, duration: Duration) -> Self {
+        Self {
+            input: input.to_path_buf(),
+            status: FileStatus::Error,
+            outputs: Vec::new(),
+            format: None,
+            rows: None,
+            columns: None,
+            input_bytes: None,
+            output_bytes: None,
+            duration_ms: duration.as_millis() as u64,
+            schema: Vec::new(),
+            rejected: None,
+            rejects: None,
+            skipped_lines: None,
+            bad_lines: None,
+            error: Some(format!("{:#}", error)),
+        }
+    }
+}
+
+impl<'a> RunReport<'a> {
+    pub fn new(stats: &'a ProcessStats) -> Self {
+        Self {
+            files: &stats.files,
+            totals: Totals {
+                files: stats.files.len(),
+                processed: stats.processed,
+                errors: stats.errors,
+                not_attempted: stats.not_attempted,
+                rows: stats.rows,
+                rejected: stats.rejected,
+                skipped_lines: stats.skipped_lines,
+                bytes_in: stats.bytes_in,
+                bytes_out: stats.bytes_out,
+                compression_ratio: stats.compression_ratio(),
+                elapsed_ms: stats.elapsed.as_millis() as u64,
+            },
+        }
+    }
+}
+
+/// Writes the report to `path` if given, and to stdout with `--output-format json`.
+pub fn emit(stats: &ProcessStats, path: Option<&Path>, format: OutputFormat) -> Result<()> {
+    let json = serde_json::to_string_pretty(&RunReport::new(stats))
+        .context("Failed to serialize report")?;
+
+    if let Some(path) = path {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).context("Failed to create report directory")?;
+        }
+        fs::write(path, &json)
+            .with_context(|| format!("Failed to write report: {}", path.display()))?;
+    }
+
+    if format == OutputFormat::Json {
+        println!("{}", json);
+    }
+
+    Ok(())
+}
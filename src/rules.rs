@@ -0,0 +1,220 @@
+use anyhow::{anyhow, Context, Result};
+use globset::GlobMatcher;
+use regex::Regex;
+use std::path::Path;
+
+use crate::config::{PostAction, RuleConfig};
+use crate::filter::compile_glob;
+use crate::quality::QualityChecks;
+use crate::reader;
+use crate::schema;
+use crate::template;
+
+enum Matcher {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn is_match(&self, relative: &Path) -> bool {
+        match self {
+            Self::Glob(glob) => glob.is_match(relative),
+            Self::Regex(regex) => regex.is_match(&relative.to_string_lossy().replace('\\', "/")),
+        }
+    }
+}
+
+/// The compiled `[[rules]]` from the configuration, in declaration order.
+pub struct RuleSet {
+    rules: Vec<(Matcher, RuleConfig)>,
+}
+
+impl RuleSet {
+    pub fn new(rules: &[RuleConfig]) -> Result<Self> {
+        let compiled = rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                let label = rule_label(rule, index);
+                let matcher = compile(rule).with_context(|| format!("Invalid rule {}", label))?;
+                validate(rule).with_context(|| format!("Invalid rule {}", label))?;
+                Ok((matcher, rule.clone()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { rules: compiled })
+    }
+
+    /// Returns the first rule matching the path relative to the input directory.
+    pub fn find(&self, relative: &Path) -> Option<&RuleConfig> {
+        self.rules
+            .iter()
+            .find(|(matcher, _)| matcher.is_match(relative))
+            .map(|(_, rule)| rule)
+    }
+}
+
+fn compile(rule: &RuleConfig) -> Result<Matcher> {
+    match (&rule.glob, &rule.regex) {
+        (Some(glob), None) => Ok(Matcher::Glob(compile_glob(glob)?.compile_matcher())),
+        (None, Some(regex)) => Ok(Matcher::Regex(
+            Regex::new(regex).with_context(|| format!("Invalid regex: {}", regex))?,
+        )),
+        (Some(_), Some(_)) => Err(anyhow!("set either 'glob' or 'regex', not both")),
+        (None, None) => Err(anyhow!("missing 'glob' or 'regex'")),
+    }
+}
+
+fn validate(rule: &RuleConfig) -> Result<()> {
+    if rule.post_action == Some(PostAction::Move) && rule.move_to.is_none() {
+        return Err(anyhow!("post_action = \"move\" requires 'move_to'"));
+    }
+
+    if let Some(delimiter) = rule.reader.csv_delimiter {
+        if !delimiter.is_ascii() {
+            return Err(anyhow!("csv_delimiter must be a single ASCII character"));
+        }
+    }
+
+    if let Some(quote_char) = rule.reader.csv_quote_char {
+        if !quote_char.is_ascii() {
+            return Err(anyhow!("csv_quote_char must be a single ASCII character"));
+        }
+    }
+
+    if rule.reader.csv_decimal_comma == Some(true) && rule.reader.csv_delimiter == Some(',') {
+        return Err(anyhow!(
+            "csv_decimal_comma cannot be used with csv_delimiter = ','"
+        ));
+    }
+
+    if let Some(ref encoding) = rule.reader.encoding {
+        reader::validate_encoding(encoding)?;
+    }
+
+    if let Some(ref output_template) = rule.output_template {
+        template::validate(output_template)?;
+    }
+
+    if let Some(ref quality) = rule.quality {
+        QualityChecks::new(quality)?;
+    }
+
+    if let Some(ref locale) = rule.locale {
+        schema::validate_locale(locale)?;
+    }
+
+    Ok(())
+}
+
+pub fn rule_label(rule: &RuleConfig, index: usize) -> String {
+    match rule.name {
+        Some(ref name) => format!("'{}'", name),
+        None => format!("#{}", index + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::processor::read_options;
+
+    fn config(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    fn matched<'a>(rules: &'a RuleSet, path: &str) -> Option<&'a str> {
+        rules.find(Path::new(path)).and_then(|r| r.name.as_deref())
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let config = config(
+            r#"
+            [[rules]]
+            name = "sales"
+            glob = "sales/*.csv"
+
+            [[rules]]
+            name = "dated"
+            regex = '^\w+/\d{4}-\d{2}\.csv$'
+
+            [[rules]]
+            name = "csv"
+            glob = "**/*.csv"
+            "#,
+        );
+        let rules = RuleSet::new(&config.rules).unwrap();
+
+        assert_eq!(matched(&rules, "sales/2024-01.csv"), Some("sales"));
+        assert_eq!(matched(&rules, "hr/2024-01.csv"), Some("dated"));
+        assert_eq!(matched(&rules, "hr/deep/2024-01.csv"), Some("csv"));
+        assert_eq!(matched(&rules, "top.csv"), Some("csv"));
+        assert_eq!(matched(&rules, "top.xlsx"), None);
+    }
+
+    #[test]
+    fn globs_do_not_cross_directories() {
+        let config = config("[[rules]]\nname = \"flat\"\nglob = \"*.csv\"\n");
+        let rules = RuleSet::new(&config.rules).unwrap();
+
+        assert_eq!(matched(&rules, "a.csv"), Some("flat"));
+        assert_eq!(matched(&rules, "sub/a.csv"), None);
+    }
+
+    #[test]
+    fn rule_reader_settings_take_precedence() {
+        let config = config(
+            r#"
+            [general]
+            default_sheet = "Data"
+
+            [processing]
+            csv_delimiter = ";"
+            csv_has_header = false
+            encoding = "windows-1252"
+
+            [[rules]]
+            glob = "*.csv"
+            [rules.reader]
+            csv_delimiter = "|"
+            sheet = "Export"
+            "#,
+        );
+
+        let options = read_options(&config, config.rules.first());
+        assert_eq!(options.csv_delimiter, Some(b'|'));
+        assert_eq!(options.sheet.as_deref(), Some("Export"));
+        // Unset values fall back to [processing] and [general]
+        assert_eq!(options.csv_has_header, Some(false));
+        assert_eq!(options.encoding.as_deref(), Some("windows-1252"));
+
+        let options = read_options(&config, None);
+        assert_eq!(options.csv_delimiter, Some(b';'));
+        assert_eq!(options.sheet.as_deref(), Some("Data"));
+    }
+
+    #[test]
+    fn invalid_rules_are_named_in_errors() {
+        let cases = [
+            (
+                "name = \"both\"\nglob = \"*\"\nregex = \".*\"",
+                "rule 'both'",
+            ),
+            ("name = \"none\"", "missing 'glob' or 'regex'"),
+            ("glob = \"*\"\npost_action = \"move\"", "rule #1"),
+            ("regex = \"(\"", "Invalid regex"),
+            (
+                "glob = \"*\"\n[rules.reader]\ncsv_delimiter = \"é\"",
+                "ASCII",
+            ),
+        ];
+
+        for (rule, expected) in cases {
+            let config = config(&format!("[[rules]]\n{}\n", rule));
+            let error = format!("{:#}", RuleSet::new(&config.rules).err().unwrap());
+            assert!(error.contains(expected), "{}: {}", rule, error);
+        }
+    }
+}
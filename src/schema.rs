@@ -0,0 +1,418 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::format::StrftimeItems;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use polars::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config::LocaleConfig;
+
+/// Column types to enforce after reading, loaded from a TOML file. A column is either
+/// a type name or a table with the type and locale settings for that column:
+///
+/// ```toml
+/// [columns]
+/// id = "int64"
+/// price = { type = "float64", decimal_separator = ",", thousands_separator = "." }
+/// created = { type = "date", date_formats = ["%d/%m/%Y"] }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchemaFile {
+    columns: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnSpec {
+    #[serde(rename = "type")]
+    dtype: String,
+
+    #[serde(default)]
+    decimal_separator: Option<char>,
+
+    #[serde(default)]
+    thousands_separator: Option<char>,
+
+    #[serde(default)]
+    date_formats: Vec<String>,
+
+    #[serde(default)]
+    datetime_formats: Vec<String>,
+}
+
+/// One column of a schema file. `locale` holds the settings the column overrides.
+#[derive(Debug)]
+pub struct SchemaColumn {
+    pub name: String,
+    pub dtype: DataType,
+    pub locale: LocaleConfig,
+}
+
+pub fn load_schema(path: &Path) -> Result<Vec<SchemaColumn>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read schema file: {}", path.display()))?;
+
+    let file: SchemaFile = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse schema file: {}", path.display()))?;
+
+    file.columns
+        .into_iter()
+        .map(|(name, value)| {
+            parse_column(&name, value)
+                .with_context(|| format!("Invalid column '{}' in {}", name, path.display()))
+        })
+        .collect()
+}
+
+fn parse_column(name: &str, value: toml::Value) -> Result<SchemaColumn> {
+    let spec = match value {
+        toml::Value::String(dtype) => ColumnSpec {
+            dtype,
+            decimal_separator: None,
+            thousands_separator: None,
+            date_formats: Vec::new(),
+            datetime_formats: Vec::new(),
+        },
+        value => value
+            .try_into()
+            .context("Expected a type name or a table")?,
+    };
+
+    let locale = LocaleConfig {
+        decimal_separator: spec.decimal_separator,
+        thousands_separator: spec.thousands_separator,
+        date_formats: spec.date_formats,
+        datetime_formats: spec.datetime_formats,
+    };
+    validate_locale(&locale)?;
+
+    Ok(SchemaColumn {
+        name: name.to_string(),
+        dtype: parse_dtype(&spec.dtype)?,
+        locale,
+    })
+}
+
+/// Casts the schema's columns. Text columns typed as numbers, dates or datetimes are
+/// read with `locale`, overridden per column by the schema file.
+pub fn apply_schema(
+    df: &mut DataFrame,
+    schema: &[SchemaColumn],
+    locale: &LocaleConfig,
+) -> Result<()> {
+    for column in schema {
+        let name = &column.name;
+        let source = df
+            .column(name)
+            .with_context(|| format!("Schema column '{}' not found", name))?;
+
+        let locale = merge(locale, &column.locale);
+        let cast = cast_column(source, &column.dtype, &locale)
+            .with_context(|| format!("Failed to cast column '{}' to {}", name, column.dtype))?;
+
+        df.with_column(cast)
+            .with_context(|| format!("Failed to replace column '{}'", name))?;
+    }
+
+    Ok(())
+}
+
+/// Columns typed as numbers, dates or datetimes that have locale settings. Readers
+/// keep these as text, since inferring `1.000` as a number would lose the thousands.
+pub fn locale_columns(schema: &[SchemaColumn], locale: &LocaleConfig) -> Vec<String> {
+    schema
+        .iter()
+        .filter(|column| {
+            let locale = merge(locale, &column.locale);
+            let numeric = column.dtype.is_primitive_numeric()
+                && (locale.decimal_separator.is_some() || locale.thousands_separator.is_some());
+            let temporal = matches!(column.dtype, DataType::Date | DataType::Datetime(_, _))
+                && !(locale.date_formats.is_empty() && locale.datetime_formats.is_empty());
+            numeric || temporal
+        })
+        .map(|column| column.name.clone())
+        .collect()
+}
+
+/// Checks that separators differ and formats parse.
+pub fn validate_locale(locale: &LocaleConfig) -> Result<()> {
+    if locale.decimal_separator.is_some() && locale.decimal_separator == locale.thousands_separator
+    {
+        return Err(anyhow!(
+            "decimal_separator and thousands_separator must differ"
+        ));
+    }
+
+    for format in locale.date_formats.iter().chain(&locale.datetime_formats) {
+        StrftimeItems::new(format)
+            .parse()
+            .map_err(|_| anyhow!("Invalid date format: {}", format))?;
+    }
+
+    Ok(())
+}
+
+/// `column` settings override `base` where they are set.
+fn merge(base: &LocaleConfig, column: &LocaleConfig) -> LocaleConfig {
+    let formats = |column: &Vec<String>, base: &Vec<String>| {
+        if column.is_empty() {
+            base.clone()
+        } else {
+            column.clone()
+        }
+    };
+
+    LocaleConfig {
+        decimal_separator: column.decimal_separator.or(base.decimal_separator),
+        thousands_separator: column.thousands_separator.or(base.thousands_separator),
+        date_formats: formats(&column.date_formats, &base.date_formats),
+        datetime_formats: formats(&column.datetime_formats, &base.datetime_formats),
+    }
+}
+
+fn cast_column(column: &Column, dtype: &DataType, locale: &LocaleConfig) -> Result<Column> {
+    if column.dtype() != &DataType::String {
+        return Ok(column.strict_cast(dtype)?);
+    }
+
+    let values = column.str()?;
+    let has_separators = locale.decimal_separator.is_some() || locale.thousands_separator.is_some();
+
+    match dtype {
+        DataType::Date if !locale.date_formats.is_empty() => {
+            let days = values
+                .iter()
+                .map(|value| {
+                    non_empty(value)
+                        .map(|v| parse_date(v, &locale.date_formats).map(days_since_epoch))
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(Column::new(column.name().clone(), days).cast(dtype)?)
+        }
+        DataType::Datetime(TimeUnit::Microseconds, None)
+            if !locale.datetime_formats.is_empty() || !locale.date_formats.is_empty() =>
+        {
+            let micros = values
+                .iter()
+                .map(|value| {
+                    non_empty(value)
+                        .map(|v| parse_datetime(v, locale).map(|t| t.and_utc().timestamp_micros()))
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(Column::new(column.name().clone(), micros).cast(dtype)?)
+        }
+        _ if dtype.is_primitive_numeric() && has_separators => {
+            let normalized: Vec<Option<String>> = values
+                .iter()
+                .map(|value| non_empty(value).map(|v| normalize_number(v, locale)))
+                .collect();
+
+            Ok(Column::new(column.name().clone(), normalized).strict_cast(dtype)?)
+        }
+        _ => Ok(column.strict_cast(dtype)?),
+    }
+}
+
+/// `1.234,56` with `.` for thousands and `,` for decimals gives `1234.56`.
+fn normalize_number(value: &str, locale: &LocaleConfig) -> String {
+    value
+        .chars()
+        .filter(|c| Some(*c) != locale.thousands_separator)
+        .map(|c| {
+            if Some(c) == locale.decimal_separator {
+                '.'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn parse_date(value: &str, formats: &[String]) -> Result<NaiveDate> {
+    formats
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+        .ok_or_else(|| anyhow!("'{}' matches none of the date formats", value))
+}
+
+/// Tries `datetime_formats`, then `date_formats` at midnight.
+fn parse_datetime(value: &str, locale: &LocaleConfig) -> Result<NaiveDateTime> {
+    locale
+        .datetime_formats
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            parse_date(value, &locale.date_formats)
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| anyhow!("'{}' matches none of the datetime formats", value))
+}
+
+fn days_since_epoch(date: NaiveDate) -> i32 {
+    (date - DateTime::UNIX_EPOCH.date_naive()).num_days() as i32
+}
+
+/// Blank text counts as null.
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn parse_dtype(name: &str) -> Result<DataType> {
+    match name.to_lowercase().as_str() {
+        "string" | "str" | "utf8" => Ok(DataType::String),
+        "bool" | "boolean" => Ok(DataType::Boolean),
+        "int32" | "i32" => Ok(DataType::Int32),
+        "int64" | "i64" | "int" => Ok(DataType::Int64),
+        "uint32" | "u32" => Ok(DataType::UInt32),
+        "uint64" | "u64" => Ok(DataType::UInt64),
+        "float32" | "f32" => Ok(DataType::Float32),
+        "float64" | "f64" | "float" => Ok(DataType::Float64),
+        "date" => Ok(DataType::Date),
+        "datetime" => Ok(DataType::Datetime(TimeUnit::Microseconds, None)),
+        other => Err(anyhow!("Unknown type: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn german() -> LocaleConfig {
+        LocaleConfig {
+            decimal_separator: Some(','),
+            thousands_separator: Some('.'),
+            date_formats: vec!["%d.%m.%Y".to_string(), "%Y-%m-%d".to_string()],
+            datetime_formats: vec!["%d.%m.%Y %H:%M".to_string()],
+        }
+    }
+
+    fn text(values: &[Option<&str>]) -> Column {
+        Column::new("value".into(), values)
+    }
+
+    #[test]
+    fn numbers_use_the_locale_separators() {
+        let cast = cast_column(
+            &text(&[Some("1.234,56"), Some(" -7 "), Some(""), None]),
+            &DataType::Float64,
+            &german(),
+        )
+        .unwrap();
+        let values: Vec<_> = cast.f64().unwrap().into_iter().collect();
+        assert_eq!(values, [Some(1234.56), Some(-7.0), None, None]);
+
+        let cast = cast_column(&text(&[Some("1.000.000")]), &DataType::Int64, &german()).unwrap();
+        assert_eq!(cast.i64().unwrap().get(0), Some(1_000_000));
+    }
+
+    #[test]
+    fn unparsable_numbers_fail_the_cast() {
+        let result = cast_column(&text(&[Some("12,5 kg")]), &DataType::Float64, &german());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn dates_try_each_format_in_order() {
+        let cast = cast_column(
+            &text(&[Some("05.03.2024"), Some("2024-03-06"), Some(" ")]),
+            &DataType::Date,
+            &german(),
+        )
+        .unwrap();
+        let days: Vec<_> = cast.to_physical_repr().i32().unwrap().into_iter().collect();
+        let expected = |d| days_since_epoch(NaiveDate::from_ymd_opt(2024, 3, d).unwrap());
+        assert_eq!(days, [Some(expected(5)), Some(expected(6)), None]);
+
+        let result = cast_column(&text(&[Some("03/05/2024")]), &DataType::Date, &german());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn datetimes_fall_back_to_dates_at_midnight() {
+        let dtype = DataType::Datetime(TimeUnit::Microseconds, None);
+        let cast = cast_column(
+            &text(&[Some("05.03.2024 14:30"), Some("05.03.2024")]),
+            &dtype,
+            &german(),
+        )
+        .unwrap();
+        let micros: Vec<_> = cast.to_physical_repr().i64().unwrap().into_iter().collect();
+        let midnight = NaiveDate::from_ymd_opt(2024, 3, 5)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_micros();
+        let half_past_two = midnight + (14 * 60 + 30) * 60 * 1_000_000;
+        assert_eq!(micros, [Some(half_past_two), Some(midnight)]);
+    }
+
+    #[test]
+    fn without_locale_settings_casts_are_plain() {
+        let cast = cast_column(
+            &text(&[Some("1.5")]),
+            &DataType::Float64,
+            &LocaleConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(cast.f64().unwrap().get(0), Some(1.5));
+
+        let numbers = Column::new("value".into(), [1i64, 2]);
+        let cast = cast_column(&numbers, &DataType::Float64, &german()).unwrap();
+        assert_eq!(cast.f64().unwrap().get(1), Some(2.0));
+    }
+
+    #[test]
+    fn column_settings_override_the_base_locale() {
+        let column: toml::Value = toml::from_str::<toml::Table>(
+            "t = { type = \"f64\", decimal_separator = \".\", thousands_separator = \",\" }",
+        )
+        .unwrap()
+        .remove("t")
+        .unwrap();
+        let column = parse_column("price", column).unwrap();
+        let merged = merge(&german(), &column.locale);
+
+        assert_eq!(merged.decimal_separator, Some('.'));
+        assert_eq!(merged.thousands_separator, Some(','));
+        assert_eq!(merged.date_formats, german().date_formats);
+        assert_eq!(normalize_number("1,234.5", &merged), "1234.5");
+    }
+
+    #[test]
+    fn locale_columns_are_typed_columns_with_settings() {
+        let schema = vec![
+            parse_column("id", "int64".into()).unwrap(),
+            parse_column("name", "string".into()).unwrap(),
+            parse_column("created", "date".into()).unwrap(),
+        ];
+
+        assert_eq!(locale_columns(&schema, &german()), ["id", "created"]);
+        assert!(locale_columns(&schema, &LocaleConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn invalid_locales_are_rejected() {
+        let same = LocaleConfig {
+            decimal_separator: Some(','),
+            thousands_separator: Some(','),
+            ..Default::default()
+        };
+        assert!(validate_locale(&same).is_err());
+
+        let bad_format = LocaleConfig {
+            date_formats: vec!["%Q".to_string()],
+            ..Default::default()
+        };
+        assert!(validate_locale(&bad_format).is_err());
+        assert!(validate_locale(&german()).is_ok());
+    }
+}
@@ -0,0 +1,487 @@
+use anyhow::{anyhow, Context, Result};
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::io::Read;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::filter::FileFilter;
+use crate::processor::Planner;
+use crate::shutdown;
+use crate::storage::{self, ObjectLocation};
+
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+const OBJECT_CREATED_PREFIX: &str = "s3:ObjectCreated:";
+/// Largest request body accepted; MinIO sends one event per request.
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct EventNotification {
+    #[serde(rename = "Records", default)]
+    records: Vec<EventRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventRecord {
+    event_name: String,
+    s3: S3Entity,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Entity {
+    bucket: BucketEntity,
+    object: ObjectEntity,
+}
+
+#[derive(Debug, Deserialize)]
+struct BucketEntity {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ObjectEntity {
+    key: String,
+    #[serde(default)]
+    size: Option<u64>,
+}
+
+struct EventOutcome {
+    converted: usize,
+    skipped: usize,
+    errors: usize,
+}
+
+pub fn serve<F>(mut config: Config, reload: F) -> Result<()>
+where
+    F: Fn() -> Result<Config>,
+{
+    let server = Server::http(&config.serve.listen)
+        .map_err(|e| anyhow!("Failed to listen on {}: {}", config.serve.listen, e))?;
+
+    let mut filter = FileFilter::new(&config.processing)?;
+    let mut planner = Planner::new(&config)?;
+
+    info!(
+        listen = %config.serve.listen,
+        output_dir = %config.general.output_dir.display(),
+        "Listening for bucket notifications"
+    );
+
+    while !shutdown::requested() {
+        if shutdown::take_reload() {
+            match reload() {
+                Ok(new_config) => {
+                    info!("Configuration reloaded");
+                    if new_config.serve.listen != config.serve.listen {
+                        warn!("Listen address changes need a restart");
+                    }
+                    // Validation has already built both once, so this does not fail
+                    filter = FileFilter::new(&new_config.processing)?;
+                    planner = Planner::new(&new_config)?;
+                    config = new_config;
+                }
+                Err(e) => error!(
+                    error = %format!("{:#}", e),
+                    "Reload failed, keeping current configuration"
+                ),
+            }
+        }
+
+        match server.recv_timeout(SIGNAL_CHECK_INTERVAL) {
+            Ok(Some(request)) => handle_request(request, &config, &filter, &planner),
+            Ok(None) => {}
+            Err(e) => error!(error = %e, "Server error"),
+        }
+    }
+
+    info!(signal = shutdown::signal_name(), "Shutdown requested, exiting");
+
+    Ok(())
+}
+
+fn handle_request(mut request: Request, config: &Config, filter: &FileFilter, planner: &Planner) {
+    if *request.method() != Method::Post {
+        respond(request, 405, "Method not allowed");
+        return;
+    }
+
+    if !is_authorized(&request, config) {
+        respond(request, 401, "Unauthorized");
+        return;
+    }
+
+    if request
+        .body_length()
+        .is_some_and(|len| len as u64 > MAX_BODY_SIZE)
+    {
+        respond(request, 413, "Request body too large");
+        return;
+    }
+
+    let mut body = String::new();
+    let read = request
+        .as_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_string(&mut body);
+    if let Err(e) = read {
+        respond(request, 400, &format!("Failed to read request body: {}", e));
+        return;
+    }
+    if body.len() as u64 > MAX_BODY_SIZE {
+        respond(request, 413, "Request body too large");
+        return;
+    }
+
+    // MinIO validates the endpoint with an empty request when the target is registered
+    if body.trim().is_empty() {
+        respond(request, 200, "OK");
+        return;
+    }
+
+    let notification: EventNotification = match serde_json::from_str(&body) {
+        Ok(n) => n,
+        Err(e) => {
+            respond(request, 400, &format!("Invalid event notification: {}", e));
+            return;
+        }
+    };
+
+    let outcome = process_notification(&notification, config, filter, planner);
+    let summary = format!(
+        "Converted: {} | Skipped: {} | Errors: {}",
+        outcome.converted, outcome.skipped, outcome.errors
+    );
+
+    // A non-2xx status makes MinIO keep the event in its queue and retry it, so only
+    // conversion failures, which may succeed on a retry, get one
+    let status = if outcome.errors > 0 { 500 } else { 200 };
+    respond(request, status, &summary);
+}
+
+fn process_notification(
+    notification: &EventNotification,
+    config: &Config,
+    filter: &FileFilter,
+    planner: &Planner,
+) -> EventOutcome {
+    let mut outcome = EventOutcome {
+        converted: 0,
+        skipped: 0,
+        errors: 0,
+    };
+
+    for record in &notification.records {
+        if !record.event_name.starts_with(OBJECT_CREATED_PREFIX) {
+            outcome.skipped += 1;
+            continue;
+        }
+
+        let input = match object_uri(record) {
+            Ok(uri) => uri,
+            Err(e) => {
+                error!(error = %format!("{:#}", e), "Invalid event record, skipped");
+                outcome.skipped += 1;
+                continue;
+            }
+        };
+
+        // Only objects below the configured input prefix are converted, since sources
+        // may be deleted afterwards. Retrying would not change that, so they are skipped
+        let base = &config.general.input_dir;
+        let Ok(relative) = input.strip_prefix(base) else {
+            warn!(
+                input = %input.display(),
+                input_dir = %base.display(),
+                "Object is outside the input directory, skipped"
+            );
+            outcome.skipped += 1;
+            continue;
+        };
+
+        if !filter.matches(relative, record.s3.object.size) {
+            outcome.skipped += 1;
+            continue;
+        }
+
+        match planner.process_file(&input, base, config) {
+            Ok(_) => outcome.converted += 1,
+            Err(e) => {
+                error!(
+                    input = %input.display(),
+                    error = %format!("{:#}", e),
+                    "Conversion failed"
+                );
+                outcome.errors += 1;
+            }
+        }
+    }
+
+    outcome
+}
+
+fn object_uri(record: &EventRecord) -> Result<PathBuf> {
+    // Keys arrive form-encoded, with spaces as '+'
+    let key = record.s3.object.key.replace('+', " ");
+    let key = percent_decode_str(&key)
+        .decode_utf8()
+        .with_context(|| format!("Invalid object key: {}", record.s3.object.key))?;
+
+    Ok(ObjectLocation {
+        bucket: record.s3.bucket.name.clone(),
+        key: key.into_owned(),
+    }
+    .to_uri())
+}
+
+/// Refuses settings that would let anyone on the network trigger conversions: a listen
+/// address other than loopback needs an `auth_token`. Notifications are only accepted
+/// for an `s3://` input directory.
+pub fn check_config(config: &Config) -> Result<()> {
+    if !storage::is_remote(&config.general.input_dir) {
+        return Err(anyhow!(
+            "serve needs an s3:// input_dir, got {}",
+            config.general.input_dir.display()
+        ));
+    }
+
+    if config.serve.auth_token.is_some() {
+        return Ok(());
+    }
+
+    let loopback = config
+        .serve
+        .listen
+        .to_socket_addrs()
+        .with_context(|| format!("Invalid listen address: {}", config.serve.listen))?
+        .all(|addr| addr.ip().is_loopback());
+
+    if !loopback {
+        return Err(anyhow!(
+            "Listening on {} needs serve.auth_token; set one or listen on 127.0.0.1",
+            config.serve.listen
+        ));
+    }
+
+    Ok(())
+}
+
+fn is_authorized(request: &Request, config: &Config) -> bool {
+    let Some(ref token) = config.serve.auth_token else {
+        return true;
+    };
+
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .map(|h| h.value.as_str().trim())
+        .map(|value| value.strip_prefix("Bearer ").unwrap_or(value))
+        .is_some_and(|value| constant_time_eq(value.as_bytes(), token.as_bytes()))
+}
+
+/// Compares without returning early, so response times do not reveal how much of a
+/// guessed token matched. Only the length can be told apart.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn respond(request: Request, status: u16, message: &str) {
+    let header = Header::from_bytes("Content-Type", "text/plain; charset=utf-8")
+        .expect("static header is valid");
+    let response = Response::from_string(message)
+        .with_status_code(status)
+        .with_header(header);
+
+    if let Err(e) = request.respond(response) {
+        error!(error = %e, "Failed to send response");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StorageBackend;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::path::Path;
+    use std::thread;
+    use tempfile::TempDir;
+
+    fn memory_config(bucket: &str, output_dir: &Path) -> Config {
+        let mut config = Config::default();
+        config.general.input_dir = PathBuf::from(format!("s3://{}/in", bucket));
+        config.general.output_dir = output_dir.to_path_buf();
+        config.storage.backend = StorageBackend::Memory;
+        config
+    }
+
+    fn put(uri: &str, content: &[u8], config: &Config) {
+        let location = ObjectLocation::parse(Path::new(uri)).unwrap();
+        let dir = TempDir::new().unwrap();
+        let local = dir.path().join("object");
+        std::fs::write(&local, content).unwrap();
+        storage::Storage::connect(&config.storage, &location.bucket)
+            .unwrap()
+            .upload(&local, &location.key)
+            .unwrap();
+    }
+
+    fn event(bucket: &str, key: &str) -> String {
+        format!(
+            r#"{{"Records": [{{"eventName": "s3:ObjectCreated:Put",
+                "s3": {{"bucket": {{"name": "{}"}}, "object": {{"key": "{}", "size": 16}}}}}}]}}"#,
+            bucket, key
+        )
+    }
+
+    /// Sends one request to a server on a free local port, handles it with `config`
+    /// and returns the response status and body.
+    fn send(config: &Config, method: &str, headers: &[&str], body: &str) -> (u16, String) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+
+        let mut raw = format!(
+            "{} /events HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            body.len()
+        );
+        for header in headers {
+            raw.push_str(header);
+            raw.push_str("\r\n");
+        }
+        raw.push_str("\r\n");
+        raw.push_str(body);
+
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(raw.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+
+        let request = server.recv().unwrap();
+        let filter = FileFilter::new(&config.processing).unwrap();
+        let planner = Planner::new(config).unwrap();
+        handle_request(request, config, &filter, &planner);
+
+        let response = client.join().unwrap();
+        let status = response.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+        (status, body)
+    }
+
+    #[test]
+    fn created_objects_are_converted() {
+        let out = TempDir::new().unwrap();
+        let config = memory_config("test-serve-convert", out.path());
+        put(
+            "s3://test-serve-convert/in/sales.csv",
+            b"id,amount\n1,10\n",
+            &config,
+        );
+
+        let (status, body) = send(
+            &config,
+            "POST",
+            &[],
+            &event("test-serve-convert", "in/sales.csv"),
+        );
+
+        assert_eq!(status, 200);
+        assert_eq!(body, "Converted: 1 | Skipped: 0 | Errors: 0");
+        assert!(out.path().join("sales.parquet").exists());
+    }
+
+    #[test]
+    fn objects_outside_the_input_prefix_are_skipped_with_success() {
+        let out = TempDir::new().unwrap();
+        let config = memory_config("test-serve-scope", out.path());
+
+        for (bucket, key) in [
+            ("test-serve-scope", "other/sales.csv"),
+            ("another-bucket", "in/sales.csv"),
+        ] {
+            let (status, body) = send(&config, "POST", &[], &event(bucket, key));
+
+            assert_eq!(status, 200);
+            assert_eq!(body, "Converted: 0 | Skipped: 1 | Errors: 0");
+        }
+    }
+
+    #[test]
+    fn failed_conversions_are_retried() {
+        let out = TempDir::new().unwrap();
+        let config = memory_config("test-serve-missing", out.path());
+
+        let (status, body) = send(
+            &config,
+            "POST",
+            &[],
+            &event("test-serve-missing", "in/gone.csv"),
+        );
+
+        assert_eq!(status, 500);
+        assert_eq!(body, "Converted: 0 | Skipped: 0 | Errors: 1");
+    }
+
+    #[test]
+    fn malformed_requests_are_refused() {
+        let out = TempDir::new().unwrap();
+        let config = memory_config("test-serve-malformed", out.path());
+
+        let (status, body) = send(&config, "POST", &[], "{not json");
+        assert_eq!(status, 400);
+        assert!(body.starts_with("Invalid event notification"));
+
+        assert_eq!(send(&config, "GET", &[], "").0, 405);
+        // The empty request MinIO sends when the target is registered
+        assert_eq!(send(&config, "POST", &[], "").0, 200);
+    }
+
+    #[test]
+    fn token_is_required_when_configured() {
+        let out = TempDir::new().unwrap();
+        let mut config = memory_config("test-serve-token", out.path());
+        config.serve.auth_token = Some("s3cret".to_string());
+
+        assert_eq!(send(&config, "POST", &[], "").0, 401);
+        assert_eq!(
+            send(&config, "POST", &["Authorization: Bearer s3cre"], "").0,
+            401
+        );
+        assert_eq!(
+            send(&config, "POST", &["Authorization: Bearer s3cret"], "").0,
+            200
+        );
+        assert_eq!(send(&config, "POST", &["Authorization: s3cret"], "").0, 200);
+    }
+
+    #[test]
+    fn listening_beyond_loopback_needs_a_token() {
+        let out = TempDir::new().unwrap();
+        let mut config = memory_config("test-serve-check", out.path());
+        assert!(check_config(&config).is_ok());
+
+        config.serve.listen = "0.0.0.0:8080".to_string();
+        assert!(check_config(&config).is_err());
+
+        config.serve.auth_token = Some("s3cret".to_string());
+        assert!(check_config(&config).is_ok());
+
+        config.general.input_dir = PathBuf::from("/data/in");
+        assert!(check_config(&config).is_err());
+    }
+
+    #[test]
+    fn token_comparison() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::flag;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+struct SignalState {
+    shutdown: Arc<AtomicBool>,
+    signal: Arc<AtomicUsize>,
+    reload: Arc<AtomicBool>,
+}
+
+static STATE: OnceLock<SignalState> = OnceLock::new();
+
+/// Registers handlers for SIGINT/SIGTERM (graceful shutdown) and SIGHUP (config reload).
+///
+/// The first SIGINT/SIGTERM only raises a flag so the current conversion can finish.
+/// A second one terminates immediately with exit code `128 + signal`.
+pub fn install() -> Result<()> {
+    if STATE.get().is_some() {
+        return Ok(());
+    }
+
+    let state = SignalState {
+        shutdown: Arc::new(AtomicBool::new(false)),
+        signal: Arc::new(AtomicUsize::new(0)),
+        reload: Arc::new(AtomicBool::new(false)),
+    };
+
+    for sig in [SIGINT, SIGTERM] {
+        // Order matters: the conditional shutdown must see the flag before it is set
+        flag::register_conditional_shutdown(sig, 128 + sig, Arc::clone(&state.shutdown))
+            .context("Failed to register signal handler")?;
+        flag::register_usize(sig, Arc::clone(&state.signal), sig as usize)
+            .context("Failed to register signal handler")?;
+        flag::register(sig, Arc::clone(&state.shutdown))
+            .context("Failed to register signal handler")?;
+    }
+
+    flag::register(SIGHUP, Arc::clone(&state.reload))
+        .context("Failed to register SIGHUP handler")?;
+
+    let _ = STATE.set(state);
+    Ok(())
+}
+
+pub fn requested() -> bool {
+    STATE
+        .get()
+        .is_some_and(|s| s.shutdown.load(Ordering::SeqCst))
+}
+
+pub fn signal_name() -> &'static str {
+    match STATE.get().map(|s| s.signal.load(Ordering::SeqCst) as i32) {
+        Some(SIGINT) => "SIGINT",
+        Some(SIGTERM) => "SIGTERM",
+        _ => "signal",
+    }
+}
+
+/// Returns true once per received SIGHUP.
+pub fn take_reload() -> bool {
+    STATE
+        .get()
+        .is_some_and(|s| s.reload.swap(false, Ordering::SeqCst))
+}
@@ -0,0 +1,539 @@
+use anyhow::{anyhow, Context, Result};
+use futures::StreamExt;
+use object_store::aws::AmazonS3Builder;
+use object_store::buffered::BufWriter;
+use object_store::memory::InMemory;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use tempfile::TempDir;
+use tokio::io::AsyncWriteExt;
+use tokio::runtime::Runtime;
+
+use crate::config::{StorageBackend, StorageConfig};
+
+const S3_SCHEME: &str = "s3://";
+const UPLOAD_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+// In-memory buckets live for the whole process so separate connections see the same objects
+static MEMORY_BUCKETS: OnceLock<Mutex<HashMap<String, Arc<InMemory>>>> = OnceLock::new();
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+static CONNECTIONS: OnceLock<Mutex<HashMap<(StorageConfig, String), Storage>>> = OnceLock::new();
+
+pub fn is_remote(path: &Path) -> bool {
+    path.to_str().is_some_and(|s| s.starts_with(S3_SCHEME))
+}
+
+/// A `s3://bucket/key` location split into its parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectLocation {
+    pub bucket: String,
+    pub key: String,
+}
+
+impl ObjectLocation {
+    pub fn parse(path: &Path) -> Result<Self> {
+        let uri = path
+            .to_str()
+            .and_then(|s| s.strip_prefix(S3_SCHEME))
+            .ok_or_else(|| anyhow!("Not an S3 URI: {}", path.display()))?;
+
+        let (bucket, key) = uri.split_once('/').unwrap_or((uri, ""));
+        if bucket.is_empty() {
+            return Err(anyhow!("Missing bucket name in: {}", path.display()));
+        }
+
+        Ok(Self {
+            bucket: bucket.to_string(),
+            key: key.trim_matches('/').to_string(),
+        })
+    }
+
+    pub fn to_uri(&self) -> PathBuf {
+        PathBuf::from(format!("{}{}/{}", S3_SCHEME, self.bucket, self.key))
+    }
+}
+
+/// A connection to one bucket. Connections are cached per bucket and storage settings,
+/// and all of them share one runtime, so staging, publishing and post-actions for a
+/// file reuse the same client.
+#[derive(Clone)]
+pub struct Storage {
+    runtime: &'static Runtime,
+    store: Arc<dyn ObjectStore>,
+}
+
+impl Storage {
+    pub fn connect(config: &StorageConfig, bucket: &str) -> Result<Self> {
+        let mut connections = CONNECTIONS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        let key = (config.clone(), bucket.to_string());
+        if let Some(storage) = connections.get(&key) {
+            return Ok(storage.clone());
+        }
+
+        let store: Arc<dyn ObjectStore> = match config.backend {
+            StorageBackend::S3 => Arc::new(build_s3(config, bucket)?),
+            StorageBackend::Memory => memory_bucket(bucket),
+        };
+        let storage = Self {
+            runtime: runtime()?,
+            store,
+        };
+
+        connections.insert(key, storage.clone());
+        Ok(storage)
+    }
+
+    /// Lists objects under `prefix` with their sizes. Without `recursive`, only direct
+    /// children are returned.
+    pub fn list(&self, prefix: &str, recursive: bool) -> Result<Vec<(String, u64)>> {
+        let prefix = (!prefix.is_empty()).then(|| ObjectPath::from(prefix));
+
+        self.runtime.block_on(async {
+            let objects = if recursive {
+                let mut objects = Vec::new();
+                let mut stream = self.store.list(prefix.as_ref());
+                while let Some(meta) = stream.next().await {
+                    objects.push(meta.context("Failed to list objects")?);
+                }
+                objects
+            } else {
+                self.store
+                    .list_with_delimiter(prefix.as_ref())
+                    .await
+                    .context("Failed to list objects")?
+                    .objects
+            };
+
+            Ok(objects
+                .into_iter()
+                .map(|meta| (meta.location.to_string(), meta.size))
+                .collect())
+        })
+    }
+
+    pub fn download(&self, key: &str, dest: &Path) -> Result<()> {
+        let location = ObjectPath::from(key);
+
+        self.runtime.block_on(async {
+            let result = self
+                .store
+                .get(&location)
+                .await
+                .with_context(|| format!("Failed to fetch object: {}", key))?;
+            let modified = SystemTime::from(result.meta.last_modified);
+            let mut stream = result.into_stream();
+
+            let mut file = File::create(dest).context("Failed to create staging file")?;
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.with_context(|| format!("Failed to download object: {}", key))?;
+                file.write_all(&chunk)
+                    .context("Failed to write staging file")?;
+            }
+
+            // Keep the object's timestamp so templates see the source modification time
+            file.set_modified(modified)
+                .context("Failed to set staging file time")?;
+            Ok(())
+        })
+    }
+
+    pub fn upload(&self, src: &Path, key: &str) -> Result<()> {
+        let mut file = File::open(src).context("Failed to open file for upload")?;
+        let mut writer = BufWriter::new(Arc::clone(&self.store), ObjectPath::from(key));
+
+        self.runtime.block_on(async {
+            let mut buf = vec![0u8; UPLOAD_CHUNK_SIZE];
+            loop {
+                let n = file
+                    .read(&mut buf)
+                    .context("Failed to read file for upload")?;
+                if n == 0 {
+                    break;
+                }
+                writer
+                    .write_all(&buf[..n])
+                    .await
+                    .with_context(|| format!("Failed to upload object: {}", key))?;
+            }
+            writer
+                .shutdown()
+                .await
+                .with_context(|| format!("Failed to complete upload: {}", key))
+        })
+    }
+
+    pub fn delete(&self, key: &str) -> Result<()> {
+        self.runtime
+            .block_on(self.store.delete(&ObjectPath::from(key)))
+            .with_context(|| format!("Failed to delete object: {}", key))
+    }
+}
+
+fn runtime() -> Result<&'static Runtime> {
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start storage runtime")?;
+
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+fn memory_bucket(bucket: &str) -> Arc<dyn ObjectStore> {
+    let mut buckets = MEMORY_BUCKETS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    let store = buckets
+        .entry(bucket.to_string())
+        .or_insert_with(|| Arc::new(InMemory::new()));
+
+    Arc::clone(store) as Arc<dyn ObjectStore>
+}
+
+fn build_s3(config: &StorageConfig, bucket: &str) -> Result<object_store::aws::AmazonS3> {
+    let mut builder = AmazonS3Builder::from_env()
+        .with_bucket_name(bucket)
+        .with_allow_http(config.allow_http)
+        .with_virtual_hosted_style_request(config.virtual_hosted_style);
+
+    if let Some(ref endpoint) = config.endpoint {
+        builder = builder.with_endpoint(endpoint);
+    }
+    if let Some(ref region) = config.region {
+        builder = builder.with_region(region);
+    }
+    if let Some(ref key_id) = config.access_key_id {
+        builder = builder.with_access_key_id(key_id);
+    }
+    if let Some(ref secret) = config.secret_access_key {
+        builder = builder.with_secret_access_key(secret);
+    }
+
+    builder.build().context("Failed to configure S3 storage")
+}
+
+/// A local copy of a file that may live in object storage.
+///
+/// Readers and the Parquet writer only deal with local paths, so remote inputs are
+/// downloaded into a temporary directory and remote outputs are written there first.
+pub struct StagedFile {
+    local: PathBuf,
+    remote: Option<ObjectLocation>,
+    _dir: Option<TempDir>,
+}
+
+impl StagedFile {
+    pub fn path(&self) -> &Path {
+        &self.local
+    }
+
+    /// The S3 URI for a downloaded file, else the local path.
+    pub fn origin(&self) -> PathBuf {
+        self.remote
+            .as_ref()
+            .map_or_else(|| self.local.clone(), ObjectLocation::to_uri)
+    }
+}
+
+/// Makes `path` available locally, downloading it when it is an S3 URI.
+pub fn stage_input(path: &Path, config: &StorageConfig) -> Result<StagedFile> {
+    if !is_remote(path) {
+        return Ok(StagedFile {
+            local: path.to_path_buf(),
+            remote: None,
+            _dir: None,
+        });
+    }
+
+    let location = ObjectLocation::parse(path)?;
+    let (dir, local) = staging_path(&location)?;
+
+    Storage::connect(config, &location.bucket)?.download(&location.key, &local)?;
+
+    Ok(StagedFile {
+        local,
+        remote: Some(location),
+        _dir: Some(dir),
+    })
+}
+
+/// Returns a local path to write `path` to; call [`publish`] with the written files afterwards.
+pub fn stage_output(path: &Path) -> Result<StagedFile> {
+    if !is_remote(path) {
+        return Ok(StagedFile {
+            local: path.to_path_buf(),
+            remote: None,
+            _dir: None,
+        });
+    }
+
+    let location = ObjectLocation::parse(path)?;
+    let (dir, local) = staging_path(&location)?;
+
+    Ok(StagedFile {
+        local,
+        remote: Some(location),
+        _dir: Some(dir),
+    })
+}
+
+/// Uploads the files written under a staged output to the remote side and returns their
+/// final locations. Local outputs are returned unchanged.
+pub fn publish(
+    staged: &StagedFile,
+    written: &[PathBuf],
+    config: &StorageConfig,
+) -> Result<Vec<PathBuf>> {
+    let Some(ref location) = staged.remote else {
+        return Ok(written.to_vec());
+    };
+
+    let storage = Storage::connect(config, &location.bucket)?;
+    let local_root = staged.local.parent().unwrap_or(Path::new(""));
+    let remote_root = Path::new(&location.key).parent().unwrap_or(Path::new(""));
+
+    written
+        .iter()
+        .map(|local| {
+            let relative = local.strip_prefix(local_root).unwrap_or(local);
+            let target = ObjectLocation {
+                bucket: location.bucket.clone(),
+                key: remote_root
+                    .join(relative)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+            };
+            storage.upload(local, &target.key)?;
+            Ok(target.to_uri())
+        })
+        .collect()
+}
+
+/// Moves a file to `dest`, where either side may be an `s3://` URI.
+pub fn move_file(src: &Path, dest: &Path, config: &StorageConfig) -> Result<()> {
+    if !is_remote(src) && !is_remote(dest) {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).context("Failed to create destination directory")?;
+        }
+        // rename fails across filesystems, fall back to copy and delete
+        if fs::rename(src, dest).is_err() {
+            fs::copy(src, dest).context("Failed to copy file")?;
+            fs::remove_file(src).context("Failed to remove source file")?;
+        }
+        return Ok(());
+    }
+
+    let staged = stage_input(src, config)?;
+
+    if is_remote(dest) {
+        let location = ObjectLocation::parse(dest)?;
+        Storage::connect(config, &location.bucket)?.upload(staged.path(), &location.key)?;
+    } else {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).context("Failed to create destination directory")?;
+        }
+        fs::copy(staged.path(), dest).context("Failed to copy file")?;
+    }
+
+    remove(src, config)
+}
+
+pub fn remove(path: &Path, config: &StorageConfig) -> Result<()> {
+    if is_remote(path) {
+        let location = ObjectLocation::parse(path)?;
+        Storage::connect(config, &location.bucket)?.delete(&location.key)
+    } else {
+        fs::remove_file(path).map_err(Into::into)
+    }
+}
+
+/// Lists the objects under an `s3://bucket/prefix` directory as full URIs with sizes.
+pub fn list_dir(
+    dir: &Path,
+    recursive: bool,
+    config: &StorageConfig,
+) -> Result<Vec<(PathBuf, u64)>> {
+    let location = ObjectLocation::parse(dir)?;
+    let objects = Storage::connect(config, &location.bucket)?.list(&location.key, recursive)?;
+
+    Ok(objects
+        .into_iter()
+        .map(|(key, size)| {
+            let uri = ObjectLocation {
+                bucket: location.bucket.clone(),
+                key,
+            }
+            .to_uri();
+            (uri, size)
+        })
+        .collect())
+}
+
+fn staging_path(location: &ObjectLocation) -> Result<(TempDir, PathBuf)> {
+    let dir = TempDir::new().context("Failed to create staging directory")?;
+    let name = Path::new(&location.key)
+        .file_name()
+        .ok_or_else(|| anyhow!("Object key has no file name: {}", location.key))?;
+    let local = dir.path().join(name);
+    Ok((dir, local))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_config() -> StorageConfig {
+        StorageConfig {
+            backend: StorageBackend::Memory,
+            ..Default::default()
+        }
+    }
+
+    /// Puts `content` at `uri` through a local temporary file.
+    fn put(uri: &str, content: &[u8], config: &StorageConfig) {
+        let location = ObjectLocation::parse(Path::new(uri)).unwrap();
+        let dir = TempDir::new().unwrap();
+        let local = dir.path().join("object");
+        fs::write(&local, content).unwrap();
+        Storage::connect(config, &location.bucket)
+            .unwrap()
+            .upload(&local, &location.key)
+            .unwrap();
+    }
+
+    fn get(uri: &str, config: &StorageConfig) -> Vec<u8> {
+        let staged = stage_input(Path::new(uri), config).unwrap();
+        fs::read(staged.path()).unwrap()
+    }
+
+    fn keys(uri: &str, config: &StorageConfig) -> Vec<PathBuf> {
+        let mut keys: Vec<_> = list_dir(Path::new(uri), true, config)
+            .unwrap()
+            .into_iter()
+            .map(|(uri, _)| uri)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn connect_reuses_the_connection_for_a_bucket() {
+        let config = memory_config();
+        let first = Storage::connect(&config, "test-connect").unwrap();
+        let second = Storage::connect(&config, "test-connect").unwrap();
+
+        assert!(Arc::ptr_eq(&first.store, &second.store));
+        assert!(std::ptr::eq(first.runtime, second.runtime));
+    }
+
+    #[test]
+    fn stage_input_downloads_remote_files() {
+        let config = memory_config();
+        put("s3://test-stage/in/data.csv", b"a,b\n1,2\n", &config);
+
+        let staged = stage_input(Path::new("s3://test-stage/in/data.csv"), &config).unwrap();
+
+        assert_eq!(fs::read(staged.path()).unwrap(), b"a,b\n1,2\n");
+        assert_eq!(staged.path().file_name().unwrap(), "data.csv");
+        assert_eq!(staged.origin(), Path::new("s3://test-stage/in/data.csv"));
+    }
+
+    #[test]
+    fn stage_input_leaves_local_files_in_place() {
+        let staged = stage_input(Path::new("/data/in.csv"), &memory_config()).unwrap();
+
+        assert_eq!(staged.path(), Path::new("/data/in.csv"));
+        assert_eq!(staged.origin(), Path::new("/data/in.csv"));
+    }
+
+    #[test]
+    fn publish_uploads_written_files_next_to_the_output() {
+        let config = memory_config();
+        let staged = stage_output(Path::new("s3://test-publish/out/data.parquet")).unwrap();
+        let part = staged
+            .path()
+            .with_file_name("year=2024")
+            .join("part.parquet");
+        fs::write(staged.path(), b"whole").unwrap();
+        fs::create_dir_all(part.parent().unwrap()).unwrap();
+        fs::write(&part, b"part").unwrap();
+
+        let published = publish(&staged, &[staged.path().to_path_buf(), part], &config).unwrap();
+
+        assert_eq!(
+            published,
+            vec![
+                PathBuf::from("s3://test-publish/out/data.parquet"),
+                PathBuf::from("s3://test-publish/out/year=2024/part.parquet"),
+            ]
+        );
+        assert_eq!(get("s3://test-publish/out/data.parquet", &config), b"whole");
+        assert_eq!(
+            get("s3://test-publish/out/year=2024/part.parquet", &config),
+            b"part"
+        );
+    }
+
+    #[test]
+    fn move_file_between_remote_locations() {
+        let config = memory_config();
+        put("s3://test-move/in/data.csv", b"content", &config);
+
+        move_file(
+            Path::new("s3://test-move/in/data.csv"),
+            Path::new("s3://test-move/done/data.csv"),
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(
+            keys("s3://test-move", &config),
+            vec![PathBuf::from("s3://test-move/done/data.csv")]
+        );
+        assert_eq!(get("s3://test-move/done/data.csv", &config), b"content");
+    }
+
+    #[test]
+    fn move_file_from_remote_to_local() {
+        let config = memory_config();
+        put("s3://test-move-local/data.csv", b"content", &config);
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("archive/data.csv");
+
+        move_file(Path::new("s3://test-move-local/data.csv"), &dest, &config).unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"content");
+        assert!(keys("s3://test-move-local", &config).is_empty());
+    }
+
+    #[test]
+    fn remove_deletes_remote_objects() {
+        let config = memory_config();
+        put("s3://test-remove/a.csv", b"a", &config);
+        put("s3://test-remove/b.csv", b"b", &config);
+
+        remove(Path::new("s3://test-remove/a.csv"), &config).unwrap();
+
+        assert_eq!(
+            keys("s3://test-remove", &config),
+            vec![PathBuf::from("s3://test-remove/b.csv")]
+        );
+    }
+}
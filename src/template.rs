@@ -0,0 +1,352 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Component, Path, PathBuf};
+
+use crate::format::FileFormat;
+
+/// Mirrors the input's relative path, which is the behavior without a template.
+pub const DEFAULT_TEMPLATE: &str = "{rel_dir}/{stem}.parquet";
+
+const VARIABLES: &[&str] = &[
+    "stem", "ext", "rel_dir", "sheet", "format", "dataset", "mtime", "date", "hash", "hash8",
+];
+
+/// Values available to an output template. Values that are `None` are left in the
+/// rendered path as their placeholder, e.g. when planning without reading the file.
+pub struct TemplateContext<'a> {
+    pub relative: &'a Path,
+    pub format: Option<FileFormat>,
+    /// The sheet read from a workbook; ignored for other formats, where `{sheet}` is empty
+    pub sheet: Option<&'a str>,
+    pub dataset: &'a str,
+    pub modified: Option<DateTime<Local>>,
+    pub now: DateTime<Local>,
+    pub content_hash: Option<String>,
+}
+
+/// Checks that a template only uses known variables and is well-formed.
+pub fn validate(template: &str) -> Result<()> {
+    for token in tokens(template)? {
+        if let Token::Variable { name, format } = token {
+            if !VARIABLES.contains(&name) {
+                return Err(anyhow!(
+                    "Unknown template variable '{{{}}}' in: {}",
+                    name,
+                    template
+                ));
+            }
+            if let Some(format) = format {
+                if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                    return Err(anyhow!("Invalid date format '{}' in: {}", format, template));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn needs_hash(template: &str) -> bool {
+    tokens(template).is_ok_and(|tokens| {
+        tokens
+            .iter()
+            .any(|t| matches!(t, Token::Variable { name, .. } if name.starts_with("hash")))
+    })
+}
+
+pub fn needs_sheet(template: &str) -> bool {
+    tokens(template).is_ok_and(|tokens| {
+        tokens
+            .iter()
+            .any(|t| matches!(t, Token::Variable { name, .. } if *name == "sheet"))
+    })
+}
+
+/// Renders `template` to a path relative to the output directory.
+pub fn render(template: &str, ctx: &TemplateContext) -> Result<PathBuf> {
+    let mut rendered = String::new();
+
+    for token in tokens(template)? {
+        match token {
+            Token::Literal(text) => rendered.push_str(&text),
+            Token::Variable { name, format } => match value(name, format, ctx) {
+                Some(value) => rendered.push_str(&value),
+                None => {
+                    rendered.push('{');
+                    rendered.push_str(name);
+                    if let Some(format) = format {
+                        rendered.push(':');
+                        rendered.push_str(format);
+                    }
+                    rendered.push('}');
+                }
+            },
+        }
+    }
+
+    normalize(&rendered).with_context(|| format!("Invalid output path from template: {}", template))
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let file = File::open(path).context("Failed to open file for hashing")?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+
+    io::copy(&mut reader, &mut hasher).context("Failed to hash file")?;
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(bytes))
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+fn value(name: &str, format: Option<&str>, ctx: &TemplateContext) -> Option<String> {
+    let relative = ctx.relative;
+
+    match name {
+        "stem" => Some(
+            relative
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        ),
+        "ext" => Some(
+            relative
+                .extension()
+                .map(|s| s.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+        ),
+        "rel_dir" => Some(
+            relative
+                .parent()
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_default(),
+        ),
+        "sheet" => match ctx.format {
+            Some(FileFormat::Xls | FileFormat::Xlsx | FileFormat::Ods) => {
+                ctx.sheet.map(str::to_string)
+            }
+            _ => Some(String::new()),
+        },
+        "format" => ctx.format.map(|f| f.name().to_string()),
+        "dataset" => Some(ctx.dataset.to_string()),
+        "mtime" => ctx
+            .modified
+            .map(|t| t.format(format.unwrap_or("%Y%m%d%H%M%S")).to_string()),
+        "date" => Some(ctx.now.format(format.unwrap_or("%Y-%m-%d")).to_string()),
+        "hash" => ctx.content_hash.clone(),
+        "hash8" => ctx.content_hash.as_ref().map(|h| h[..8].to_string()),
+        _ => None,
+    }
+}
+
+/// Drops empty and `.` components so an empty `{rel_dir}` does not produce `/file`,
+/// and refuses paths that would escape the output directory.
+fn normalize(rendered: &str) -> Result<PathBuf> {
+    let mut path = PathBuf::new();
+
+    for component in Path::new(rendered).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir | Component::RootDir => {}
+            Component::ParentDir | Component::Prefix(_) => {
+                return Err(anyhow!("path leaves the output directory: {}", rendered));
+            }
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        return Err(anyhow!("empty output path"));
+    }
+
+    Ok(path)
+}
+
+enum Token<'a> {
+    Literal(String),
+    Variable {
+        name: &'a str,
+        format: Option<&'a str>,
+    },
+}
+
+/// Splits a template into literals and `{name}` / `{name:format}` variables.
+/// `{{` and `}}` produce literal braces.
+fn tokens(template: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut rest = template;
+
+    while let Some(pos) = rest.find(['{', '}']) {
+        literal.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            literal.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+
+        if tail.starts_with('}') {
+            return Err(anyhow!("Unmatched '}}' in template: {}", template));
+        }
+
+        let end = tail
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed '{{' in template: {}", template))?;
+        let inner = &tail[1..end];
+        let (name, format) = match inner.split_once(':') {
+            Some((name, format)) => (name, Some(format)),
+            None => (inner, None),
+        };
+
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(std::mem::take(&mut literal)));
+        }
+        tokens.push(Token::Variable { name, format });
+        rest = &tail[end + 1..];
+    }
+
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn context<'a>(relative: &'a Path, sheet: Option<&'a str>) -> TemplateContext<'a> {
+        TemplateContext {
+            relative,
+            format: FileFormat::from_path(relative),
+            sheet,
+            dataset: "sales",
+            modified: Some(Local.with_ymd_and_hms(2024, 3, 5, 14, 30, 0).unwrap()),
+            now: Local.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap(),
+            content_hash: Some("0123456789abcdef".to_string()),
+        }
+    }
+
+    fn render_with(template: &str, relative: &str, sheet: Option<&str>) -> PathBuf {
+        render(template, &context(Path::new(relative), sheet)).unwrap()
+    }
+
+    #[test]
+    fn default_template_mirrors_the_relative_path() {
+        assert_eq!(
+            render_with(DEFAULT_TEMPLATE, "2024/q1/data.csv", None),
+            Path::new("2024/q1/data.parquet")
+        );
+        assert_eq!(
+            render_with(DEFAULT_TEMPLATE, "data.csv", None),
+            Path::new("data.parquet")
+        );
+    }
+
+    #[test]
+    fn expands_path_and_dataset_variables() {
+        assert_eq!(
+            render_with(
+                "{dataset}/{format}/{stem}.{ext}.parquet",
+                "in/Data.CSV",
+                None
+            ),
+            Path::new("sales/csv/Data.csv.parquet")
+        );
+    }
+
+    #[test]
+    fn expands_dates_with_formats() {
+        assert_eq!(
+            render_with(
+                "{date}/{mtime}/{date:%Y}/{mtime:%H%M}.parquet",
+                "a.csv",
+                None
+            ),
+            Path::new("2024-06-01/20240305143000/2024/1430.parquet")
+        );
+    }
+
+    #[test]
+    fn expands_hashes() {
+        assert_eq!(
+            render_with("{hash8}/{hash}.parquet", "a.csv", None),
+            Path::new("01234567/0123456789abcdef.parquet")
+        );
+    }
+
+    #[test]
+    fn sheet_is_the_workbook_sheet_and_empty_otherwise() {
+        assert_eq!(
+            render_with("{stem}_{sheet}.parquet", "book.xlsx", Some("Sales")),
+            Path::new("book_Sales.parquet")
+        );
+        assert_eq!(
+            render_with("{stem}_{sheet}.parquet", "data.csv", Some("Sales")),
+            Path::new("data_.parquet")
+        );
+    }
+
+    #[test]
+    fn unknown_values_stay_as_placeholders() {
+        let mut ctx = context(Path::new("book.xlsx"), None);
+        ctx.content_hash = None;
+        ctx.modified = None;
+
+        assert_eq!(
+            render("{sheet}/{hash8}/{mtime:%Y}.parquet", &ctx).unwrap(),
+            Path::new("{sheet}/{hash8}/{mtime:%Y}.parquet")
+        );
+    }
+
+    #[test]
+    fn braces_can_be_escaped() {
+        assert_eq!(
+            render_with("{{x}}_{stem}.parquet", "a.csv", None),
+            Path::new("{x}_a.parquet")
+        );
+    }
+
+    #[test]
+    fn refuses_paths_leaving_the_output_directory() {
+        let ctx = context(Path::new("a.csv"), None);
+
+        assert!(render("../{stem}.parquet", &ctx).is_err());
+        assert!(render("{rel_dir}", &ctx).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_variables_and_bad_syntax() {
+        assert!(validate("{stem}/{date:%Y}.parquet").is_ok());
+        assert!(validate("{name}.parquet").is_err());
+        assert!(validate("{stem.parquet").is_err());
+        assert!(validate("stem}.parquet").is_err());
+        assert!(validate("{date:%Q}.parquet").is_err());
+    }
+
+    #[test]
+    fn detects_hash_and_sheet_variables() {
+        assert!(needs_hash("{hash8}.parquet"));
+        assert!(!needs_hash("{stem}.parquet"));
+        assert!(needs_sheet("{stem}_{sheet}.parquet"));
+        assert!(!needs_sheet("{stem}.parquet"));
+    }
+}
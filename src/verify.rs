@@ -0,0 +1,391 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use polars::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::{StorageConfig, VerifyMode};
+use crate::converter::{prepare, ConvertOptions};
+use crate::format::FileFormat;
+use crate::parquet_info::collect_files;
+use crate::storage;
+
+/// Reads `input` again the way it was converted with `options`, including schema,
+/// lineage and quality checks, and compares the rows that passed with an existing
+/// Parquet file or partitioned dataset. Lineage columns are not compared. Returns the
+/// number of rows and columns checked.
+pub fn verify_file(
+    input: &Path,
+    output: &Path,
+    options: &ConvertOptions,
+    mode: VerifyMode,
+    storage_config: &StorageConfig,
+) -> Result<(usize, usize)> {
+    let format = FileFormat::from_path(input)
+        .ok_or_else(|| anyhow!("Unsupported format: {:?}", input.extension()))?;
+
+    let staged_input = storage::stage_input(input, storage_config)?;
+    let df = prepare(
+        staged_input.path(),
+        &staged_input.origin(),
+        format,
+        options,
+        Utc::now(),
+    )
+    .with_context(|| format!("Failed to read {}", input.display()))?
+    .df;
+
+    let files = collect_files(&[output.to_path_buf()])?;
+    let partition_by = files
+        .first()
+        .map(|file| partition_columns(output, file))
+        .unwrap_or_default();
+
+    let staged_outputs = files
+        .iter()
+        .map(|file| storage::stage_input(file, storage_config))
+        .collect::<Result<Vec<_>>>()?;
+    let local_outputs: Vec<PathBuf> = staged_outputs
+        .iter()
+        .map(|staged| staged.path().to_path_buf())
+        .collect();
+
+    let added = options
+        .lineage
+        .as_ref()
+        .map(|lineage| lineage.columns().to_vec())
+        .unwrap_or_default();
+    verify_outputs(&df, &local_outputs, &partition_by, &added, mode)?;
+
+    Ok((df.height(), df.width() - added.len()))
+}
+
+/// Reads the written Parquet files back and compares them with the DataFrame they
+/// were written from. Partition columns live in the directory names, not in the
+/// files, so they are left out of the comparison, as are the `added` columns, which
+/// are dropped from both sides.
+pub fn verify_outputs(
+    expected: &DataFrame,
+    outputs: &[PathBuf],
+    partition_by: &[String],
+    added: &[&str],
+    mode: VerifyMode,
+) -> Result<()> {
+    if mode == VerifyMode::Off {
+        return Ok(());
+    }
+
+    let source = expected.drop_many(added.iter().copied());
+    let expected = source.drop_many(partition_by);
+    let mut problems = Vec::new();
+
+    let mut parts = Vec::with_capacity(outputs.len());
+    for path in outputs {
+        parts.push(read_back(path)?.drop_many(added.iter().copied()));
+    }
+
+    let rows: usize = parts.iter().map(|df| df.height()).sum();
+    if rows != expected.height() {
+        problems.push(format!(
+            "row count {} in Parquet, {} in source",
+            rows,
+            expected.height()
+        ));
+    }
+
+    for (path, part) in outputs.iter().zip(&parts) {
+        problems.extend(
+            compare_schema(&expected, part)
+                .into_iter()
+                .map(|p| format!("{}: {}", path.display(), p)),
+        );
+    }
+
+    if mode == VerifyMode::Checksum && problems.is_empty() {
+        if partition_by.is_empty() {
+            let source_sums = checksums(&expected);
+            for part in &parts {
+                let columns = expected.get_columns().iter().zip(checksums(part));
+                for ((column, written), source) in columns.zip(&source_sums) {
+                    if written != *source {
+                        problems.push(format!("column '{}': checksum differs", column.name()));
+                    }
+                }
+            }
+        } else {
+            // Each partition keeps the source order of its rows, but the files may be
+            // listed in any order, so the partitions are matched up by sorting
+            let mut written: Vec<_> = parts.iter().map(checksums).collect();
+            let mut source_parts: Vec<_> = source
+                .partition_by_stable(partition_by, false)
+                .context("Failed to partition DataFrame")?
+                .iter()
+                .map(checksums)
+                .collect();
+            written.sort();
+            source_parts.sort();
+            if written != source_parts {
+                problems.push("checksum differs in at least one partition".to_string());
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("Verification failed: {}", problems.join("; ")))
+    }
+}
+
+/// Partition columns of a Hive-style dataset, from the `col=value` directories
+/// between `root` and `file`.
+pub fn partition_columns(root: &Path, file: &Path) -> Vec<String> {
+    let relative = file.strip_prefix(root).unwrap_or(file);
+
+    relative
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .filter_map(|c| match c {
+            Component::Normal(part) => part
+                .to_str()
+                .and_then(|p| p.split_once('='))
+                .map(|(name, _)| name.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn read_back(path: &Path) -> Result<DataFrame> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open Parquet file: {}", path.display()))?;
+
+    ParquetReader::new(file)
+        .finish()
+        .with_context(|| format!("Failed to read back Parquet file: {}", path.display()))
+}
+
+fn compare_schema(expected: &DataFrame, written: &DataFrame) -> Vec<String> {
+    let expected_names: Vec<_> = expected.get_column_names();
+    let written_names: Vec<_> = written.get_column_names();
+
+    if expected_names != written_names {
+        return vec![format!(
+            "columns [{}] in Parquet, [{}] in source",
+            join(&written_names),
+            join(&expected_names)
+        )];
+    }
+
+    expected
+        .get_columns()
+        .iter()
+        .zip(written.get_columns())
+        .filter(|(e, w)| e.dtype() != w.dtype())
+        .map(|(e, w)| {
+            format!(
+                "column '{}': {} in Parquet, {} in source",
+                e.name(),
+                w.dtype(),
+                e.dtype()
+            )
+        })
+        .collect()
+}
+
+/// SHA-256 of each column's values in row order, so reordered rows are caught too.
+fn checksums(df: &DataFrame) -> Vec<[u8; 32]> {
+    df.get_columns()
+        .iter()
+        .map(|column| {
+            let mut hasher = Sha256::new();
+            for value in column.as_materialized_series().iter() {
+                match value {
+                    AnyValue::Null => hasher.update([0]),
+                    value => {
+                        let text = value.to_string();
+                        hasher.update([1]);
+                        hasher.update((text.len() as u64).to_le_bytes());
+                        hasher.update(text.as_bytes());
+                    }
+                }
+            }
+            hasher.finalize().into()
+        })
+        .collect()
+}
+
+fn join(names: &[&PlSmallStr]) -> String {
+    names
+        .iter()
+        .map(|n| n.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::processor::{run_job, Planner};
+    use crate::writer::{write_parquet, write_partitioned};
+
+    fn sales() -> DataFrame {
+        df!(
+            "region" => ["north", "south", "north", "south"],
+            "amount" => [Some(10i64), Some(20), None, Some(40)],
+        )
+        .unwrap()
+    }
+
+    fn write(df: &DataFrame, dir: &Path) -> PathBuf {
+        let path = dir.join("sales.parquet");
+        write_parquet(&mut df.clone(), &path, &[]).unwrap();
+        path
+    }
+
+    #[test]
+    fn checksum_accepts_identical_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(&sales(), dir.path());
+
+        verify_outputs(&sales(), &[path], &[], &[], VerifyMode::Checksum).unwrap();
+    }
+
+    #[test]
+    fn checksum_catches_reordered_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let reversed = sales().reverse();
+        let path = write(&reversed, dir.path());
+
+        let outputs = [path];
+        verify_outputs(&sales(), &outputs, &[], &[], VerifyMode::Basic).unwrap();
+        let err = verify_outputs(&sales(), &outputs, &[], &[], VerifyMode::Checksum).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("column 'region': checksum differs"));
+    }
+
+    #[test]
+    fn checksum_tells_null_from_the_text_null() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = df!("name" => [Some("a"), None]).unwrap();
+        let path = write(
+            &df!("name" => [Some("a"), Some("null")]).unwrap(),
+            dir.path(),
+        );
+
+        assert!(verify_outputs(&source, &[path], &[], &[], VerifyMode::Checksum).is_err());
+    }
+
+    #[test]
+    fn checksum_matches_partitions_in_any_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let partition_by = vec!["region".to_string()];
+        let mut files = write_partitioned(
+            &sales(),
+            &dir.path().join("sales.parquet"),
+            &partition_by,
+            &[],
+        )
+        .unwrap();
+        files.reverse();
+
+        verify_outputs(&sales(), &files, &partition_by, &[], VerifyMode::Checksum).unwrap();
+    }
+
+    /// Converts `in/sales.csv` through a `;` rule with a German-locale schema and
+    /// verifies the output the way the `verify` command does.
+    fn convert_and_verify(extra: &str) -> (usize, Result<(usize, usize)>) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir(root.join("in")).unwrap();
+        std::fs::write(
+            root.join("in/sales.csv"),
+            "id;amount;when\n1;1.234,5;05.03.2024\n2;-3,25;06.03.2024\n3;7;07.03.2024\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("schema.toml"),
+            r#"
+            [columns]
+            amount = { type = "f64", decimal_separator = ",", thousands_separator = "." }
+            when = { type = "date", date_formats = ["%d.%m.%Y"] }
+            "#,
+        )
+        .unwrap();
+
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [general]
+            input_dir = "{root}/in"
+            output_dir = "{root}/out"
+            {extra}
+
+            [[rules]]
+            glob = "*.csv"
+            schema_file = "{root}/schema.toml"
+            [rules.reader]
+            csv_delimiter = ";"
+            "#,
+            root = root.display(),
+        ))
+        .unwrap();
+
+        let input = root.join("in/sales.csv");
+        let job = Planner::new(&config)
+            .unwrap()
+            .plan(&input, &config.general.input_dir, &config);
+        let result = run_job(&job, &config).unwrap();
+        assert_eq!(result.schema[1], ("amount".to_string(), "f64".to_string()));
+
+        let verified = verify_file(
+            &input,
+            &result.outputs[0],
+            &job.options,
+            VerifyMode::Checksum,
+            &config.storage,
+        );
+        (result.rejected, verified)
+    }
+
+    #[test]
+    fn files_converted_through_a_schema_rule_verify() {
+        let (rejected, verified) = convert_and_verify("");
+
+        assert_eq!(rejected, 0);
+        assert_eq!(verified.unwrap(), (3, 3));
+    }
+
+    #[test]
+    fn lineage_columns_are_not_compared() {
+        let (_, verified) = convert_and_verify("[lineage]\nenabled = true");
+
+        assert_eq!(verified.unwrap(), (3, 3));
+    }
+
+    #[test]
+    fn rejected_rows_are_left_out() {
+        let (rejected, verified) = convert_and_verify(
+            r#"
+            [lineage]
+            enabled = true
+
+            [quality.columns.amount]
+            min = 0
+            "#,
+        );
+
+        assert_eq!(rejected, 1);
+        assert_eq!(verified.unwrap(), (2, 3));
+    }
+
+    #[test]
+    fn partition_columns_come_from_the_directory_names() {
+        let root = Path::new("out/sales");
+        let file = root.join("year=2024/region=north/part-0.parquet");
+
+        assert_eq!(partition_columns(root, &file), ["year", "region"]);
+    }
+}
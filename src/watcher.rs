@@ -1,35 +1,96 @@
-use anyhow::{Context, Result};
-use notify::{Config as NotifyConfig, RecommendedWatcher, RecursiveMode, Watcher};
-use std::sync::mpsc::channel;
-use std::time::Duration;
-
-use crate::config::Config;
-use crate::format::FileFormat;
-use crate::processor::{get_output_path, process_batch, process_file};
-
-pub fn watch(config: &Config) -> Result<()> {
-    let input_dir = &config.general.input_dir;
-    let output_dir = &config.general.output_dir;
-
-    println!("Watching directory: {}", input_dir.display());
-    println!("Output: {}", output_dir.display());
-    println!(
-        "Supported extensions: {:?}",
-        FileFormat::supported_extensions()
-    );
-    println!("Press Ctrl+C to stop.");
-    println!("---");
+use anyhow::{anyhow, Context, Result};
+use notify::event::ModifyKind;
+use notify::{Config as NotifyConfig, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+use crate::config::{Config, WatchBackend};
+use crate::filter::FileFilter;
+use crate::metrics;
+use crate::processor::{process_batch, Planner};
+use crate::shutdown;
+use crate::storage;
+
+mod poll;
+
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+enum WatchExit {
+    Stop,
+    Reload(Box<Config>),
+}
+
+/// What a pending SIGHUP did to the running watch.
+enum Reload {
+    Unchanged,
+    Applied,
+    Restart(WatchExit),
+}
+
+pub fn watch<F>(mut config: Config, reload: F) -> Result<()>
+where
+    F: Fn() -> Result<Config>,
+{
+    // The endpoint outlives reloads; a new address needs a restart
+    if let Some(ref listen) = config.metrics.listen {
+        metrics::serve(listen)?;
+    }
+
+    loop {
+        if storage::is_remote(&config.general.input_dir) {
+            return Err(anyhow!(
+                "Watch mode needs a local input directory, got: {}",
+                config.general.input_dir.display()
+            ));
+        }
+
+        print_header(&config);
+
+        let exit = match config.watch.backend {
+            WatchBackend::Native => watch_native(config, &reload)?,
+            WatchBackend::Poll => poll::watch(config, &reload)?,
+        };
 
+        match exit {
+            WatchExit::Stop => break,
+            WatchExit::Reload(new_config) => config = *new_config,
+        }
+    }
+
+    if shutdown::requested() {
+        info!(signal = shutdown::signal_name(), "Shutdown requested, exiting");
+    }
+
+    Ok(())
+}
+
+fn watch_native<F>(config: Config, reload: &F) -> Result<WatchExit>
+where
+    F: Fn() -> Result<Config>,
+{
     // Process existing files first
-    process_batch(config)?;
+    process_batch(&config, false)?;
 
-    println!("---");
-    println!("Waiting for new files...");
+    if shutdown::requested() {
+        return Ok(WatchExit::Stop);
+    }
+
+    info!("Waiting for new files");
+
+    watch_events(config, reload)
+}
 
+fn watch_events<F>(mut config: Config, reload: &F) -> Result<WatchExit>
+where
+    F: Fn() -> Result<Config>,
+{
     let (tx, rx) = channel();
 
     let poll_interval = Duration::from_secs(config.watch.poll_interval_secs);
-    let debounce = Duration::from_millis(config.watch.debounce_ms);
 
     let mut watcher =
         RecommendedWatcher::new(tx, NotifyConfig::default().with_poll_interval(poll_interval))
@@ -42,34 +103,153 @@ pub fn watch(config: &Config) -> Result<()> {
     };
 
     watcher
-        .watch(input_dir, mode)
+        .watch(&config.general.input_dir, mode)
         .context("Failed to start watching")?;
 
+    // Events carry absolute paths, so relative paths are taken from the canonical directory
+    let input_root = config
+        .general
+        .input_dir
+        .canonicalize()
+        .context("Failed to resolve input directory")?;
+
+    let mut filter = FileFilter::new(&config.processing)?;
+    let mut planner = Planner::new(&config)?;
+
+    // Paths seen in events, with the time of the latest one. A file is converted once
+    // no event has arrived for it for `debounce_ms`, so it is likely completely written.
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+
     loop {
-        match rx.recv() {
-            Ok(Ok(event)) => {
-                for path in event.paths {
-                    if path.is_file() && FileFormat::is_supported(&path) {
-                        // Wait to ensure file is completely written
-                        std::thread::sleep(debounce);
+        if shutdown::requested() {
+            return Ok(WatchExit::Stop);
+        }
 
-                        let output = get_output_path(&path, input_dir, output_dir);
+        match check_reload(&mut config, reload) {
+            Reload::Unchanged => {}
+            // Validation has already built both once, so this does not fail
+            Reload::Applied => {
+                filter = FileFilter::new(&config.processing)?;
+                planner = Planner::new(&config)?;
+            }
+            Reload::Restart(exit) => return Ok(exit),
+        }
 
-                        if let Err(e) = process_file(&path, &output, config) {
-                            eprintln!("[ERROR] {}: {}", path.display(), e);
-                        }
+        match rx.recv_timeout(SIGNAL_CHECK_INTERVAL) {
+            Ok(Ok(event)) if is_write(&event.kind) => {
+                for path in event.paths {
+                    let relative = path.strip_prefix(&input_root).unwrap_or(&path);
+                    if path.is_file() && filter.matches(relative, None) {
+                        pending.insert(path, Instant::now());
                     }
                 }
             }
+            Ok(Ok(_)) => {}
             Ok(Err(e)) => {
-                eprintln!("[WATCH ERROR] {:?}", e);
+                error!(error = %e, "Watch error");
             }
+            Err(RecvTimeoutError::Timeout) => {}
             Err(e) => {
-                eprintln!("[CHANNEL ERROR] {:?}", e);
+                error!(error = %e, "Watch channel closed");
+                return Ok(WatchExit::Stop);
+            }
+        }
+
+        let debounce = Duration::from_millis(config.watch.debounce_ms);
+        let mut ready: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, seen)| seen.elapsed() >= debounce)
+            .map(|(path, _)| path.clone())
+            .collect();
+        ready.sort();
+
+        for path in ready {
+            metrics::set_queue_depth(pending.len());
+            if shutdown::requested() {
                 break;
             }
+
+            pending.remove(&path);
+            process_event_path(&path, &input_root, &config, &filter, &planner);
         }
+        metrics::set_queue_depth(pending.len());
     }
+}
 
-    Ok(())
+/// Files created, written or moved in. Reading a file while converting it raises
+/// access events, which must not queue it again.
+fn is_write(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+    )
+}
+
+/// Converts a file a watch event reported, if it still exists and passes the filters.
+fn process_event_path(
+    path: &Path,
+    input_root: &Path,
+    config: &Config,
+    filter: &FileFilter,
+    planner: &Planner,
+) {
+    let relative = path.strip_prefix(input_root).unwrap_or(path);
+    let Ok(metadata) = fs::metadata(path) else {
+        return;
+    };
+    if !metadata.is_file() || !filter.matches(relative, Some(metadata.len())) {
+        return;
+    }
+
+    if let Err(e) = planner.process_file(path, input_root, config) {
+        error!(
+            input = %path.display(),
+            error = %format!("{:#}", e),
+            "Conversion failed"
+        );
+    }
+}
+
+/// Applies a pending SIGHUP reload. Returns an exit when the watch has to be restarted.
+fn check_reload<F>(config: &mut Config, reload: &F) -> Reload
+where
+    F: Fn() -> Result<Config>,
+{
+    if !shutdown::take_reload() {
+        return Reload::Unchanged;
+    }
+
+    match reload() {
+        Ok(new_config) => {
+            info!("Configuration reloaded");
+
+            // A different directory, recursion mode or backend needs a fresh watcher
+            if new_config.general.input_dir != config.general.input_dir
+                || new_config.general.recursive != config.general.recursive
+                || new_config.watch.backend != config.watch.backend
+            {
+                return Reload::Restart(WatchExit::Reload(Box::new(new_config)));
+            }
+            *config = new_config;
+            Reload::Applied
+        }
+        Err(e) => {
+            error!(
+                error = %format!("{:#}", e),
+                "Reload failed, keeping current configuration"
+            );
+            Reload::Unchanged
+        }
+    }
+}
+
+fn print_header(config: &Config) {
+    info!(
+        input_dir = %config.general.input_dir.display(),
+        output_dir = %config.general.output_dir.display(),
+        extensions = ?config.processing.extensions,
+        backend = ?config.watch.backend,
+        "Watching directory"
+    );
 }
@@ -0,0 +1,228 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tracing::{error, info};
+
+use super::{check_reload, Reload, WatchExit, SIGNAL_CHECK_INTERVAL};
+use crate::config::Config;
+use crate::metrics;
+use crate::processor::{collect_inputs, relative_path, Planner};
+use crate::shutdown;
+use crate::storage;
+
+const DEFAULT_STATE_FILE: &str = ".parse_to_parquet_state.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    size: u64,
+    modified_ms: u64,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        Some(Self {
+            size: meta.len(),
+            modified_ms: modified.as_millis() as u64,
+        })
+    }
+}
+
+/// Files already handled, keyed by their path below the input directory, so
+/// restarts only pick up new or changed files.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PollState {
+    files: BTreeMap<PathBuf, FileStamp>,
+}
+
+impl PollState {
+    fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read state file: {}", path.display()))?;
+
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse state file: {}", path.display()))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self).context("Failed to serialize state")?;
+
+        let mut tmp_path = path.as_os_str().to_os_string();
+        tmp_path.push(".tmp");
+
+        fs::write(&tmp_path, content)
+            .with_context(|| format!("Failed to write state file: {}", path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to write state file: {}", path.display()))
+    }
+}
+
+pub(super) fn watch<F>(mut config: Config, reload: &F) -> Result<WatchExit>
+where
+    F: Fn() -> Result<Config>,
+{
+    let state_path = state_path(&config)?;
+    let mut state = PollState::load(&state_path)?;
+    let mut pending = HashMap::new();
+    let mut planner = Planner::new(&config)?;
+
+    info!(
+        interval_secs = config.watch.poll_interval_secs,
+        state_file = %state_path.display(),
+        "Polling for changes"
+    );
+
+    loop {
+        if shutdown::requested() {
+            state.save(&state_path)?;
+            return Ok(WatchExit::Stop);
+        }
+
+        match check_reload(&mut config, reload) {
+            Reload::Unchanged => {}
+            Reload::Applied => planner = Planner::new(&config)?,
+            Reload::Restart(exit) => {
+                state.save(&state_path)?;
+                return Ok(exit);
+            }
+        }
+
+        if scan(&config, &planner, &mut state, &mut pending)? {
+            state.save(&state_path)?;
+        }
+
+        sleep_until_next_scan(Duration::from_secs(config.watch.poll_interval_secs));
+    }
+}
+
+/// Converts new and changed files and returns whether `state` changed.
+/// The state is saved once per scan rather than after every file.
+fn scan(
+    config: &Config,
+    planner: &Planner,
+    state: &mut PollState,
+    pending: &mut HashMap<PathBuf, FileStamp>,
+) -> Result<bool> {
+    let mut seen = HashSet::new();
+    let mut changed = false;
+
+    for (path, _) in collect_inputs(config)? {
+        if shutdown::requested() {
+            return Ok(changed);
+        }
+
+        let Some(stamp) = FileStamp::read(&path) else {
+            continue;
+        };
+        let key = relative_path(&path, &config.general.input_dir);
+        seen.insert(key.clone());
+
+        if state.files.get(&key) == Some(&stamp) {
+            continue;
+        }
+
+        // Only convert once a file looks the same on two consecutive scans,
+        // so files still being copied are left alone
+        if pending.insert(key.clone(), stamp) != Some(stamp) {
+            continue;
+        }
+        pending.remove(&key);
+
+        if let Err(e) = planner.process_file(&path, &config.general.input_dir, config) {
+            error!(
+                input = %path.display(),
+                error = %format!("{:#}", e),
+                "Conversion failed, will retry when the file changes"
+            );
+        }
+
+        state.files.insert(key, stamp);
+        changed = true;
+    }
+
+    // Forget files that were deleted or moved away
+    let before = state.files.len();
+    state.files.retain(|path, _| seen.contains(path));
+    pending.retain(|path, _| seen.contains(path));
+
+    // Files seen once are converted on the next scan
+    metrics::set_queue_depth(pending.len());
+
+    Ok(changed || state.files.len() != before)
+}
+
+fn state_path(config: &Config) -> Result<PathBuf> {
+    if let Some(ref path) = config.watch.state_file {
+        return Ok(path.clone());
+    }
+
+    if storage::is_remote(&config.general.output_dir) {
+        return Err(anyhow!(
+            "watch.state_file must be set when the output directory is remote"
+        ));
+    }
+
+    fs::create_dir_all(&config.general.output_dir).context("Failed to create output directory")?;
+
+    Ok(config.general.output_dir.join(DEFAULT_STATE_FILE))
+}
+
+fn sleep_until_next_scan(interval: Duration) {
+    let start = Instant::now();
+
+    while start.elapsed() < interval && !shutdown::requested() {
+        std::thread::sleep(SIGNAL_CHECK_INTERVAL.min(interval));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_persists_and_unchanged_files_are_not_reconverted() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_dir = dir.path().join("in");
+        let output_dir = dir.path().join("out");
+        fs::create_dir(&input_dir).unwrap();
+        fs::write(input_dir.join("a.csv"), "id,name\n1,x\n").unwrap();
+
+        let mut config = Config::default();
+        config.general.input_dir = input_dir.clone();
+        config.general.output_dir = output_dir.clone();
+        let planner = Planner::new(&config).unwrap();
+        let state_path = dir.path().join("state.json");
+        let output = output_dir.join("a.parquet");
+
+        let mut state = PollState::default();
+        let mut pending = HashMap::new();
+        // Seen once, then converted on the next scan
+        assert!(!scan(&config, &planner, &mut state, &mut pending).unwrap());
+        assert!(!output.exists());
+        assert!(scan(&config, &planner, &mut state, &mut pending).unwrap());
+        assert!(output.exists());
+        state.save(&state_path).unwrap();
+
+        // Keys are relative to the input directory, however it is spelled
+        let state = PollState::load(&state_path).unwrap();
+        assert_eq!(state.files.keys().collect::<Vec<_>>(), [Path::new("a.csv")]);
+
+        fs::remove_file(&output).unwrap();
+        config.general.input_dir = dir.path().join(".").join("in");
+        let mut state = state;
+        let mut pending = HashMap::new();
+        for _ in 0..2 {
+            assert!(!scan(&config, &planner, &mut state, &mut pending).unwrap());
+        }
+        assert!(!output.exists());
+    }
+}
@@ -1,18 +1,111 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use polars::prelude::*;
+use polars_parquet::write::KeyValue;
 use std::fs::{self, File};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-pub fn write_parquet(df: &mut DataFrame, path: &Path) -> Result<()> {
+/// Rows per row group, the same default as polars' `ParquetWriter`.
+const ROW_GROUP_SIZE: usize = 512 * 512;
+
+/// Writes `df` with `metadata` added to the footer's key-value metadata.
+pub fn write_parquet(df: &mut DataFrame, path: &Path, metadata: &[(String, String)]) -> Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).context("Failed to create output directory")?;
     }
 
+    // Write to a temporary file and rename, so an interrupted run never leaves a partial Parquet
+    let tmp_path = temp_path(path);
+
+    if let Err(e) = write_file(df, &tmp_path, metadata) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    fs::rename(&tmp_path, path).context("Failed to move Parquet file into place")
+}
+
+/// Writes a Hive-style partitioned dataset: `<output stem>/<col>=<value>/part-0.parquet`.
+/// Partition columns are encoded in the directory names and dropped from the files.
+pub fn write_partitioned(
+    df: &DataFrame,
+    output: &Path,
+    partition_by: &[String],
+    metadata: &[(String, String)],
+) -> Result<Vec<PathBuf>> {
+    let root = output.with_extension("");
+
+    let parts = df
+        .partition_by_stable(partition_by, true)
+        .context("Failed to partition DataFrame")?;
+
+    let mut written = Vec::with_capacity(parts.len());
+
+    for part in parts {
+        let mut dir = root.clone();
+        for column in partition_by {
+            let value = part
+                .column(column)
+                .context("Partition column not found")?
+                .get(0)
+                .context("Empty partition")?;
+            dir.push(format!("{}={}", column, partition_value(&value)));
+        }
+
+        let mut part = part.drop_many(partition_by);
+        let path = dir.join("part-0.parquet");
+        write_parquet(&mut part, &path, metadata)?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+fn partition_value(value: &AnyValue) -> String {
+    let raw = match value {
+        AnyValue::Null => return "__HIVE_DEFAULT_PARTITION__".to_string(),
+        AnyValue::String(s) => s.to_string(),
+        AnyValue::StringOwned(s) => s.to_string(),
+        other => other.to_string(),
+    };
+
+    raw.replace('%', "%25").replace('/', "%2F")
+}
+
+/// `ParquetWriter::finish` has no way to add key-value metadata, so the footer is
+/// written through the underlying file writer instead. Each slice of `ROW_GROUP_SIZE`
+/// rows is written as one row group.
+fn write_file(df: &mut DataFrame, path: &Path, metadata: &[(String, String)]) -> Result<()> {
     let file = File::create(path).context("Failed to create Parquet file")?;
 
-    ParquetWriter::new(file)
-        .finish(df)
+    let mut batched = ParquetWriter::new(file)
+        .batched(df.schema())
+        .context("Failed to write Parquet file")?;
+
+    for offset in (0..df.height()).step_by(ROW_GROUP_SIZE) {
+        let mut group = df.slice(offset as i64, ROW_GROUP_SIZE);
+        group.as_single_chunk_par();
+        batched
+            .write_batch(&group)
+            .context("Failed to write Parquet file")?;
+    }
+
+    let key_values = metadata
+        .iter()
+        .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+        .collect();
+
+    batched
+        .get_writer()
+        .lock()
+        .map_err(|_| anyhow!("Parquet writer lock poisoned"))?
+        .end(Some(key_values))
         .context("Failed to write Parquet file")?;
 
     Ok(())
 }
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}